@@ -0,0 +1,189 @@
+//! Packaged `.crate` archives.
+//!
+//! A `.crate` file is a gzip-compressed tarball published by `cargo package`, with all files of
+//! the package under a `<name>-<version>` directory. The Rust files of the archive are read into
+//! memory, the archive is never unpacked to disk.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use crate::{
+    count_in_parallel, count_source,
+    manifest::{ManifestError, Package},
+    modules::SourceFiles,
+    CountOptions, DirStats, ModuleTree, VisitorContext,
+};
+
+/// Files of an archive.
+struct ArchiveFiles {
+    /// Paths of all files.
+    paths: BTreeSet<PathBuf>,
+    /// Contents of the Rust files by path.
+    sources: BTreeMap<PathBuf, Vec<u8>>,
+    /// Path and contents of the `Cargo.toml` at the top of the archive.
+    manifest: Option<(PathBuf, String)>,
+}
+
+/// Counts lines of the Rust files of a `.crate` archive. Files are keyed by their path in the
+/// archive. Files reachable from the crate roots of the targets from the manifest of the package
+/// are assigned the context of their target, like files of packages on disk, other files are
+/// classified by their path in the package. Category rules are matched against the path in the
+/// package.
+pub fn count_crate_archive(path: impl AsRef<Path>, options: &CountOptions) -> io::Result<DirStats> {
+    let files = read_files(File::open(path.as_ref())?)?;
+    let package = read_package(&files)?;
+    let module_tree = package
+        .as_ref()
+        .map(|package| {
+            ModuleTree::from_targets_in(
+                package.targets.iter().cloned(),
+                &options.visitor.test_attributes,
+                SourceFiles::InMemory(&files.sources),
+            )
+        })
+        .unwrap_or_default();
+    let package_dir = package.map(|package| package.dir);
+
+    let paths = files.sources.keys().cloned().collect();
+    let files = count_in_parallel(paths, options.jobs(), |entry_path| {
+        let package_path = package_dir
+            .as_ref()
+            .and_then(|dir| entry_path.strip_prefix(dir).ok())
+            .unwrap_or(entry_path);
+        // Main code is classified by path conventions, e.g. `src/tests.rs` is tests
+        let category = options
+            .category_rules
+            .category(package_path)
+            .cloned()
+            .unwrap_or_else(|| {
+                module_tree
+                    .context(entry_path)
+                    .filter(|context| *context != VisitorContext::Main)
+                    .unwrap_or_else(|| VisitorContext::from_file_path(package_path))
+                    .into()
+            });
+        Ok(count_source(
+            &files.sources[entry_path][..],
+            category,
+            options,
+        ))
+    })?;
+
+    Ok(DirStats {
+        files,
+        unreachable: None,
+    })
+}
+
+/// Reads the paths of all files of the archive, with the contents of the Rust files and of the
+/// manifest.
+fn read_files(reader: impl Read) -> io::Result<ArchiveFiles> {
+    let mut files = ArchiveFiles {
+        paths: BTreeSet::new(),
+        sources: BTreeMap::new(),
+        manifest: None,
+    };
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+        if path.components().count() == 2 && path.ends_with("Cargo.toml") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            files.manifest = Some((path.clone(), contents));
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents)?;
+            files.sources.insert(path.clone(), contents);
+        }
+        files.paths.insert(path);
+    }
+
+    Ok(files)
+}
+
+/// Reads the package from the manifest of the archive, with targets found among the files of the
+/// archive. Returns `None` if the archive has no manifest.
+fn read_package(files: &ArchiveFiles) -> io::Result<Option<Package>> {
+    let Some((manifest_path, contents)) = &files.manifest else {
+        return Ok(None);
+    };
+    let dir = manifest_path.parent().unwrap_or(Path::new(""));
+    Package::from_listed_files(contents, dir, &files.paths).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            ManifestError::Parse(manifest_path.clone(), e),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+    use tar::{Builder, Header};
+
+    use crate::{test_utils::TempTree, Category};
+
+    use super::*;
+
+    #[test]
+    fn count_archive_entries() {
+        let tree = TempTree::new("archive");
+        let path = tree.path().join("a-0.1.0.crate");
+        let mut builder = Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        let files = [
+            (
+                "a-0.1.0/Cargo.toml",
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[[test]]\nname = \"it\"\npath = \"it/main.rs\"\n",
+            ),
+            ("a-0.1.0/src/lib.rs", "fn lib() {}\n\n#[cfg(test)]\nmod tests {}\n"),
+            ("a-0.1.0/it/main.rs", "mod helpers;\nfn it() {}\n"),
+            ("a-0.1.0/it/helpers.rs", "fn helper() {}\n"),
+            ("a-0.1.0/examples/demo.rs", "fn main() {}\n"),
+            ("a-0.1.0/build.rs", "fn main() {}\n"),
+            ("a-0.1.0/README.md", "# a\n"),
+        ];
+        for (name, contents) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let stats = count_crate_archive(&path, &CountOptions::default()).unwrap();
+
+        assert_eq!(
+            stats.files.keys().collect::<Vec<_>>(),
+            vec![
+                Path::new("a-0.1.0/build.rs"),
+                Path::new("a-0.1.0/examples/demo.rs"),
+                Path::new("a-0.1.0/it/helpers.rs"),
+                Path::new("a-0.1.0/it/main.rs"),
+                Path::new("a-0.1.0/src/lib.rs"),
+            ]
+        );
+        let totals = stats.totals();
+        assert_eq!(totals[Category::Main].code, 1);
+        assert_eq!(totals[Category::Tests].code, 5);
+        assert_eq!(totals[Category::Examples].code, 1);
+        assert_eq!(totals[Category::BuildScripts].code, 1);
+    }
+}
@@ -0,0 +1,99 @@
+//! Outer and inner attributes of items.
+
+use crate::{
+    cfg::CfgExpr,
+    lexer::{Token, TokenKind},
+};
+
+/// Attributes of well-known test frameworks marking a function as a test.
+pub const TEST_ATTRIBUTES: &[&str] = &[
+    "test",
+    "tokio::test",
+    "async_std::test",
+    "rstest",
+    "rstest::rstest",
+    "test_case",
+    "test_case::test_case",
+    "proptest",
+    "test_strategy::proptest",
+    "wasm_bindgen_test",
+    "wasm_bindgen_test::wasm_bindgen_test",
+    "sqlx::test",
+];
+
+/// Attribute like `#[cfg(test)]` or `#[path = "foo.rs"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    /// Path of the attribute, e.g. `cfg` or `tokio::test`.
+    pub path: String,
+    /// Tokens inside the delimiters following the path, or after the `=` sign.
+    pub args: Vec<Token>,
+}
+
+impl Attribute {
+    /// Parses the tokens between the brackets of an attribute. Trivia tokens must be filtered
+    /// out beforehand.
+    pub fn parse(tokens: &[Token]) -> Option<Self> {
+        let is_path_separator = |pos: usize| {
+            tokens
+                .get(pos..pos + 2)
+                .is_some_and(|t| t.iter().all(|t| t.kind == TokenKind::Punct(':')))
+        };
+
+        let mut pos = if is_path_separator(0) { 2 } else { 0 };
+        let mut segments = Vec::new();
+        loop {
+            let token = tokens.get(pos)?;
+            if !matches!(token.kind, TokenKind::Ident | TokenKind::RawIdent) {
+                return None;
+            }
+            segments.push(token.text.trim_start_matches("r#"));
+            pos += 1;
+
+            if !is_path_separator(pos) {
+                break;
+            }
+            pos += 2;
+        }
+        let path = segments.join("::");
+
+        let args = match tokens.get(pos).map(|t| t.kind) {
+            None => vec![],
+            Some(TokenKind::Punct('=')) => tokens[pos + 1..].to_vec(),
+            Some(TokenKind::Punct('(' | '[' | '{')) => {
+                let last = tokens.last().map(|t| t.kind);
+                if !matches!(last, Some(TokenKind::Punct(')' | ']' | '}'))) {
+                    return None;
+                }
+                tokens[pos + 1..tokens.len() - 1].to_vec()
+            }
+            Some(_) => return None,
+        };
+
+        Some(Self { path, args })
+    }
+
+    /// Returns the string value of an attribute like `#[path = "foo.rs"]`.
+    pub fn value(&self) -> Option<&str> {
+        match self.args.as_slice() {
+            [token] => token.string_value(),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the item the attribute is attached to is a benchmark.
+    pub fn is_bench(&self) -> bool {
+        self.path == "bench"
+    }
+
+    /// Returns true if the item the attribute is attached to is compiled only for tests. Besides
+    /// [`TEST_ATTRIBUTES`], any path from `test_attributes` marks a test.
+    pub fn is_test(&self, test_attributes: &[String]) -> bool {
+        match self.path.as_str() {
+            "cfg" => CfgExpr::parse(&self.args).is_some_and(|expr| expr.requires_test()),
+            path => {
+                TEST_ATTRIBUTES.contains(&path) || test_attributes.iter().any(|attr| attr == path)
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Conditional compilation predicates.
+//!
+//! Parses the predicate of `#[cfg(...)]` attributes into an expression tree and answers whether
+//! the annotated code is compiled only for tests.
+
+use crate::lexer::{Token, TokenKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgExpr {
+    /// `true` or `false`.
+    Bool(bool),
+    /// `test`, `unix`, ...
+    Name(String),
+    /// `feature = "x"`, `target_os = "linux"`, ...
+    KeyValue(String, String),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Parses the tokens between the parentheses of `cfg(...)`. Trivia tokens must be filtered
+    /// out beforehand.
+    pub fn parse(tokens: &[Token]) -> Option<Self> {
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr()?;
+        parser.eat(TokenKind::Punct(','));
+
+        parser.is_at_end().then_some(expr)
+    }
+
+    /// Returns true if the predicate can be satisfied only when compiling tests.
+    ///
+    /// Only predicates that require `test` to be true are classified as tests. Code under
+    /// `cfg(any(test, fuzzing))` is compiled in builds with the `fuzzing` option that are not test
+    /// builds, so it counts as main code.
+    ///
+    /// Predicates that cannot be satisfied at all (like `cfg(all(test, not(test)))`) describe
+    /// code that is never compiled and are not considered test-only.
+    pub fn requires_test(&self) -> bool {
+        self.eval(false) == Some(false) && self.eval(true) != Some(false)
+    }
+
+    /// Evaluates the predicate for the given value of the `test` option. Any other option is
+    /// unknown, in which case the result is `None` unless it is decided regardless of that
+    /// option.
+    fn eval(&self, test: bool) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            Self::Name(name) if name == "test" => Some(test),
+            Self::Name(_) | Self::KeyValue(..) => None,
+            Self::Not(expr) => expr.eval(test).map(|value| !value),
+            Self::All(exprs) => {
+                let values: Vec<_> = exprs.iter().map(|expr| expr.eval(test)).collect();
+                if values.contains(&Some(false)) {
+                    Some(false)
+                } else if values.iter().all(Option::is_some) {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            Self::Any(exprs) => {
+                let values: Vec<_> = exprs.iter().map(|expr| expr.eval(test)).collect();
+                if values.contains(&Some(true)) {
+                    Some(true)
+                } else if values.iter().all(Option::is_some) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn expr(&mut self) -> Option<CfgExpr> {
+        let name = self.ident()?;
+
+        if self.eat(TokenKind::Punct('=')) {
+            let value = self.string()?;
+            return Some(CfgExpr::KeyValue(name, value));
+        }
+
+        match name.as_str() {
+            "all" | "any" | "not" if self.eat(TokenKind::Punct('(')) => {
+                let mut exprs = Vec::new();
+                while !self.eat(TokenKind::Punct(')')) {
+                    exprs.push(self.expr()?);
+                    if !self.eat(TokenKind::Punct(',')) {
+                        self.expect(TokenKind::Punct(')'))?;
+                        break;
+                    }
+                }
+
+                match name.as_str() {
+                    "all" => Some(CfgExpr::All(exprs)),
+                    "any" => Some(CfgExpr::Any(exprs)),
+                    _ => {
+                        let [expr] = <[CfgExpr; 1]>::try_from(exprs).ok()?;
+                        Some(CfgExpr::Not(Box::new(expr)))
+                    }
+                }
+            }
+            "true" => Some(CfgExpr::Bool(true)),
+            "false" => Some(CfgExpr::Bool(false)),
+            _ => Some(CfgExpr::Name(name)),
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos)?;
+        let name = match token.kind {
+            TokenKind::Ident => token.text.clone(),
+            TokenKind::RawIdent => token.text.trim_start_matches("r#").to_string(),
+            _ => return None,
+        };
+
+        self.pos += 1;
+        Some(name)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let value = self.tokens.get(self.pos)?.string_value()?;
+
+        self.pos += 1;
+        Some(value.to_string())
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        let matches = self.tokens.get(self.pos).is_some_and(|t| t.kind == kind);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Option<()> {
+        self.eat(kind).then_some(())
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::Lexer;
+
+    use super::*;
+
+    fn requires_test(predicate: &str) -> bool {
+        let tokens: Vec<_> = Lexer::new(predicate.as_bytes())
+            .filter(|token| !token.kind.is_trivia())
+            .collect();
+        CfgExpr::parse(&tokens)
+            .unwrap_or_else(|| panic!("failed to parse {predicate}"))
+            .requires_test()
+    }
+
+    #[test]
+    fn parse() {
+        let tokens: Vec<_> = Lexer::new(r#"all(test, not(unix), feature = "x",)"#.as_bytes())
+            .filter(|token| !token.kind.is_trivia())
+            .collect();
+
+        assert_eq!(
+            CfgExpr::parse(&tokens),
+            Some(CfgExpr::All(vec![
+                CfgExpr::Name("test".to_string()),
+                CfgExpr::Not(Box::new(CfgExpr::Name("unix".to_string()))),
+                CfgExpr::KeyValue("feature".to_string(), "x".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_only_predicates() {
+        assert!(requires_test("test"));
+        assert!(requires_test(r#"all(test, feature = "x")"#));
+        assert!(requires_test("all(unix, any(test, all(test, windows)))"));
+        assert!(requires_test("not(not(test))"));
+        assert!(requires_test("any(test, false)"));
+    }
+
+    #[test]
+    fn not_test_only_predicates() {
+        assert!(!requires_test("unix"));
+        assert!(!requires_test("any(test, fuzzing)"));
+        assert!(!requires_test("not(test)"));
+        assert!(!requires_test("all(test, not(test))"));
+        assert!(!requires_test("any()"));
+    }
+}
@@ -1,6 +1,8 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
+use cargo_warloc::{CategoryRule, Threshold};
 use clap::{Parser, ValueEnum};
+use serde::Deserialize;
 
 /// Wise analysis of Rust lines of code
 ///
@@ -9,6 +11,106 @@ use clap::{Parser, ValueEnum};
 /// tests) and examples.
 #[derive(Debug, Parser)]
 pub struct Cli {
+    /// Files or directories to count. Defaults to the current directory. Subtotals of each path
+    /// are printed if more than one is given
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+    /// Reads the list of files to count from a file, or from standard input if `-` is given.
+    /// Paths are separated by new lines or NUL characters
+    #[arg(long, value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+    /// Counts the Rust files of a packaged `.crate` archive, as published by `cargo package`,
+    /// without unpacking it. Subtotals are printed like for paths. Can be given multiple times
+    #[arg(
+        long = "crate-file",
+        value_name = "FILE",
+        conflicts_with_all = ["module_tree", "by_target"]
+    )]
+    pub crate_files: Vec<PathBuf>,
+    /// Path to the `Cargo.toml` of the workspace or package to count. Counts the packages of the
+    /// workspace, with subtotals for each package
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["paths", "files_from", "crate_files", "stdin"]
+    )]
+    pub manifest_path: Option<PathBuf>,
+    /// Package of the workspace to count, with subtotals for each package. Can be given multiple
+    /// times
+    #[arg(
+        short,
+        long = "package",
+        value_name = "SPEC",
+        conflicts_with_all = ["paths", "files_from", "crate_files", "stdin"]
+    )]
+    pub packages: Vec<String>,
+    /// Counts all packages of the workspace, with subtotals for each package
+    #[arg(long, conflicts_with_all = ["paths", "files_from", "crate_files", "stdin"])]
+    pub workspace: bool,
+    /// Package to leave out when counting the whole workspace. Can be given multiple times
+    #[arg(long, value_name = "SPEC", requires = "workspace")]
+    pub exclude: Vec<String>,
+    /// Glob pattern of the files to count when searching directories, e.g. `src/**`. Other files
+    /// are left out. Can be given multiple times
+    #[arg(long = "include-glob", value_name = "GLOB")]
+    pub include_globs: Vec<String>,
+    /// Glob pattern of the files or directories to leave out when searching directories, e.g.
+    /// `third_party/`. Can be given multiple times
+    #[arg(long = "exclude-glob", value_name = "GLOB")]
+    pub exclude_globs: Vec<String>,
+    /// Counts files ignored by `.gitignore`, `.ignore` and `.warlocignore` files
+    #[arg(long, overrides_with = "ignore")]
+    pub no_ignore: bool,
+    /// Leaves out ignored files, overriding `no-ignore` of the settings
+    #[arg(long, overrides_with = "no_ignore")]
+    pub ignore: bool,
+    /// Counts hidden files and searches hidden directories
+    #[arg(long, overrides_with = "no_hidden")]
+    pub hidden: bool,
+    /// Leaves out hidden files and directories, overriding `hidden` of the settings
+    #[arg(long, overrides_with = "hidden")]
+    pub no_hidden: bool,
+    /// Follows symbolic links when searching directories
+    #[arg(long, overrides_with = "no_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// Does not follow symbolic links, overriding `follow-symlinks` of the settings
+    #[arg(long, overrides_with = "follow_symlinks")]
+    pub no_follow_symlinks: bool,
+    /// Rule assigning files matching a glob to a category, as `GLOB=CATEGORY`, e.g.
+    /// `**/test_utils.rs=tests`. Globs are matched against the path relative to the package of
+    /// the file. The first matching rule wins over the built-in classification. Categories are
+    /// main, tests, examples, benches, build_scripts and generated. Can be given multiple times
+    #[arg(long = "category-rule", value_name = "GLOB=CATEGORY")]
+    pub category_rules: Vec<CategoryRule>,
+    /// Maximum lines of code of a category, as `CATEGORY=LINES`, e.g. `main=20000`. Exits with an
+    /// error after printing the stats if the counted files have more. A later threshold of a
+    /// category replaces earlier ones. Can be given multiple times
+    #[arg(long = "threshold", value_name = "CATEGORY=LINES")]
+    pub thresholds: Vec<Threshold>,
+    /// Counts the sources of the dependencies locked in `Cargo.lock` of the workspace instead of
+    /// the workspace itself, with subtotals for each dependency. Sources are looked up offline in
+    /// the `vendor` directory, the registry source cache and the git checkouts of Cargo.
+    /// Dependencies without sources available locally are reported
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "paths",
+            "files_from",
+            "crate_files",
+            "stdin",
+            "packages",
+            "workspace",
+            "by_target"
+        ]
+    )]
+    pub dependencies: bool,
+    /// Counts Rust source code read from standard input instead of files
+    #[arg(long, conflicts_with_all = ["paths", "files_from", "crate_files"])]
+    pub stdin: bool,
+    /// Path of the file the source code read with `--stdin` comes from. Used to find out whether
+    /// the code is main code, tests, examples, ... the same way as for files
+    #[arg(long, value_name = "PATH", requires = "stdin")]
+    pub stdin_path: Option<PathBuf>,
     /// Prints out contents of the analyzed files line by line with the category the line was
     /// assigned
     #[arg(long, hide = true)]
@@ -16,13 +118,60 @@ pub struct Cli {
     /// If set, will print out stats for each file separately
     #[arg(long)]
     pub by_file: bool,
-    /// Output format to print to standard output
-    #[arg(short, long, default_value_t = OutputFormat::Tabular)]
-    pub output_format: OutputFormat,
+    /// If set, will print out stats for each Cargo target (lib, each bin, each integration test,
+    /// ...) separately. Files shared by several targets are counted for each of them
+    #[arg(long)]
+    pub by_target: bool,
+    /// If set, will print out the totals of each directory, summed up the directory tree
+    #[arg(long)]
+    pub by_dir: bool,
+    /// Maximum depth of the directories printed with `--by-dir`. Deeper directories are summed
+    /// into their ancestor at this depth
+    #[arg(long, value_name = "N", requires = "by_dir")]
+    pub depth: Option<usize>,
+    /// If set, counts only files that are part of a crate: starting from the crate roots of all
+    /// Cargo targets, follows `mod` declarations. Files that are not reachable this way are
+    /// reported separately
+    #[arg(long, overrides_with = "no_module_tree")]
+    pub module_tree: bool,
+    /// Counts all files found, overriding `module-tree` of the settings
+    #[arg(long, overrides_with = "module_tree")]
+    pub no_module_tree: bool,
+    /// If set, files marked as generated (`@generated`, prost, bindgen, ...) are skipped and
+    /// lines of `include!` calls of files from `OUT_DIR` are not counted
+    #[arg(long, overrides_with = "no_exclude_generated")]
+    pub exclude_generated: bool,
+    /// Counts generated code, overriding `exclude-generated` of the settings
+    #[arg(long, overrides_with = "exclude_generated")]
+    pub no_exclude_generated: bool,
+    /// If set, counts only the files `cargo package` would publish, following the
+    /// `package.include` and `package.exclude` rules of each package manifest. Files outside of
+    /// packages are not counted
+    #[arg(long, overrides_with = "no_packaged")]
+    pub packaged: bool,
+    /// Counts files whether or not they are published, overriding `packaged` of the settings
+    #[arg(long, overrides_with = "packaged")]
+    pub no_packaged: bool,
+    /// Number of threads searching directories and counting files. `0` uses one thread per
+    /// available core [default: 0]
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+    /// Output format to print to standard output [default: tabular]
+    #[arg(short, long)]
+    pub output_format: Option<OutputFormat>,
+    /// Settings file to use instead of the `warloc.toml` at the workspace root
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Path of an attribute marking test functions, e.g. `my_framework::test`. Attributes of
+    /// well-known test frameworks (`tokio::test`, `rstest`, `test_case`, ...) are always
+    /// recognized. Can be given multiple times
+    #[arg(long = "test-attribute", value_name = "PATH")]
+    pub test_attributes: Vec<String>,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
 #[value(rename_all = "lower")]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     #[default]
     Tabular,
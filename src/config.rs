@@ -0,0 +1,316 @@
+//! Project configuration.
+//!
+//! Settings are read from `[workspace.metadata.warloc]` in the manifest of the workspace root,
+//! from a `warloc.toml` file at the workspace root and from `[package.metadata.warloc]` in the
+//! manifest of the package of the current directory (or of `--manifest-path`), in this order.
+//! Later sources take precedence, and flags given on the command line take precedence over all
+//! of them. Lists are concatenated, with category rules of higher precedence first, as the first
+//! matching rule wins. The settings apply to everything counted in a run: packages selected with
+//! `-p` or `--workspace` don't use their own `[package.metadata.warloc]`.
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use cargo_warloc::{CategoryRule, Threshold};
+use serde::Deserialize;
+
+use crate::cli::{Cli, OutputFormat};
+
+/// Name of the configuration file at the workspace root.
+pub const CONFIG_FILE_NAME: &str = "warloc.toml";
+
+/// Settings of a project. Every setting corresponds to a command line flag of the same name.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub include_glob: Vec<String>,
+    pub exclude_glob: Vec<String>,
+    pub no_ignore: Option<bool>,
+    pub hidden: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub test_attribute: Vec<String>,
+    pub category_rule: Vec<CategoryRule>,
+    pub threshold: Vec<Threshold>,
+    pub module_tree: Option<bool>,
+    pub exclude_generated: Option<bool>,
+    pub packaged: Option<bool>,
+    pub output_format: Option<OutputFormat>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {e}", path.display()),
+            Self::Parse(path, e) => {
+                write!(f, "invalid warloc settings in {}: {e}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Deserialize)]
+struct RawManifest {
+    package: Option<RawMetadataTable>,
+    workspace: Option<RawMetadataTable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMetadataTable {
+    metadata: Option<RawMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMetadata {
+    warloc: Option<Config>,
+}
+
+impl Config {
+    /// Loads the settings of the project in `dir`: the closest package containing the directory
+    /// and its workspace. `config_path` replaces the `warloc.toml` of the workspace if given.
+    pub fn load(dir: &Path, config_path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut manifests = dir
+            .ancestors()
+            .map(|dir| dir.join("Cargo.toml"))
+            .filter(|path| path.is_file());
+        let package: Option<(PathBuf, RawManifest)> = manifests
+            .next()
+            .map(|path| read_toml(&path).map(|manifest| (path, manifest)))
+            .transpose()?;
+        let is_workspace = package
+            .as_ref()
+            .is_some_and(|(_, manifest)| manifest.workspace.is_some());
+
+        // Like Cargo, the closest manifest with a `[workspace]` table is the workspace root. Other
+        // manifests above the package belong to unrelated projects and are skipped, even if they
+        // can't be read.
+        let outer_workspace = if is_workspace {
+            None
+        } else {
+            manifests.find_map(|path| {
+                let manifest: RawManifest = read_toml(&path).ok()?;
+                manifest.workspace.is_some().then_some((path, manifest))
+            })
+        };
+        let workspace = if is_workspace {
+            package.as_ref()
+        } else {
+            outer_workspace.as_ref()
+        };
+        let root = workspace
+            .or(package.as_ref())
+            .and_then(|(path, _)| path.parent())
+            .unwrap_or(dir);
+        let package = package
+            .as_ref()
+            .and_then(|(_, manifest)| manifest.package.as_ref());
+
+        let file_config = match config_path {
+            Some(path) => Some(read_toml(path)?),
+            None => {
+                let path = root.join(CONFIG_FILE_NAME);
+                path.is_file().then(|| read_toml(&path)).transpose()?
+            }
+        };
+
+        let metadata = |table: Option<&RawMetadataTable>| {
+            table
+                .and_then(|table| table.metadata.as_ref())
+                .and_then(|metadata| metadata.warloc.clone())
+        };
+        let sources = [
+            metadata(workspace.and_then(|(_, manifest)| manifest.workspace.as_ref())),
+            file_config,
+            metadata(package),
+        ];
+
+        Ok(sources
+            .into_iter()
+            .flatten()
+            .fold(Self::default(), |config, source| source.merge(config)))
+    }
+
+    /// Merges settings of lower precedence into these settings.
+    fn merge(self, lower: Self) -> Self {
+        let concat = |lower: Vec<String>, higher: Vec<String>| [lower, higher].concat();
+        Self {
+            include_glob: concat(lower.include_glob, self.include_glob),
+            exclude_glob: concat(lower.exclude_glob, self.exclude_glob),
+            no_ignore: self.no_ignore.or(lower.no_ignore),
+            hidden: self.hidden.or(lower.hidden),
+            follow_symlinks: self.follow_symlinks.or(lower.follow_symlinks),
+            test_attribute: concat(lower.test_attribute, self.test_attribute),
+            category_rule: [self.category_rule, lower.category_rule].concat(),
+            threshold: [lower.threshold, self.threshold].concat(),
+            module_tree: self.module_tree.or(lower.module_tree),
+            exclude_generated: self.exclude_generated.or(lower.exclude_generated),
+            packaged: self.packaged.or(lower.packaged),
+            output_format: self.output_format.or(lower.output_format),
+        }
+    }
+
+    /// Fills in the command line arguments that were not given with the settings. A setting
+    /// turned on is turned off again by the negating flag, e.g. `--no-hidden`.
+    pub fn apply(self, args: &mut Cli) {
+        let merge_flag = |flag: &mut bool, negated: bool, setting: Option<bool>| {
+            *flag = !negated && (*flag || setting.unwrap_or(false));
+        };
+
+        args.include_globs = [self.include_glob, args.include_globs.clone()].concat();
+        args.exclude_globs = [self.exclude_glob, args.exclude_globs.clone()].concat();
+        merge_flag(&mut args.no_ignore, args.ignore, self.no_ignore);
+        merge_flag(&mut args.hidden, args.no_hidden, self.hidden);
+        merge_flag(
+            &mut args.follow_symlinks,
+            args.no_follow_symlinks,
+            self.follow_symlinks,
+        );
+        args.test_attributes = [self.test_attribute, args.test_attributes.clone()].concat();
+        args.category_rules = [args.category_rules.clone(), self.category_rule].concat();
+        args.thresholds = [self.threshold, args.thresholds.clone()].concat();
+        merge_flag(&mut args.module_tree, args.no_module_tree, self.module_tree);
+        merge_flag(
+            &mut args.exclude_generated,
+            args.no_exclude_generated,
+            self.exclude_generated,
+        );
+        merge_flag(&mut args.packaged, args.no_packaged, self.packaged);
+        args.output_format = args.output_format.clone().or(self.output_format);
+    }
+}
+
+fn read_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{cli::CargoCli, test_utils::TempTree};
+
+    #[test]
+    fn merge_sources() {
+        let tree = TempTree::new("config");
+        let root = tree.path();
+
+        tree.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.metadata.warloc]\n\
+             test-attribute = [\"ws::test\"]\noutput-format = \"csv\"\nhidden = true\n\
+             category-rule = [\"**/testing/**=tests\"]\nthreshold = [\"main=100\"]\n",
+        );
+        tree.write(
+            "warloc.toml",
+            "exclude-glob = [\"vendor/\"]\noutput-format = \"json\"\nhidden = false\n",
+        );
+        tree.write(
+            "a/Cargo.toml",
+            "[package]\nname = \"a\"\n\n[package.metadata.warloc]\ntest-attribute = [\"a::test\"]\n\
+             category-rule = [\"src/testing/**=main\"]\nthreshold = [\"main=200\"]\n",
+        );
+        tree.write(
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\n\n[package.metadata.warloc]\nhiden = true\n",
+        );
+        tree.write("c/warloc.toml", "output-format = \"xml\"\n");
+        tree.write(
+            "d/warloc.toml",
+            "category-rule = [\"fixtures/**=test data\"]\n",
+        );
+
+        let config = Config::load(&root.join("a/src"), None).unwrap();
+        let explicit = Config::load(&root.join("a"), Some(&root.join("c/warloc.toml")));
+        let misspelled = Config::load(&root.join("b"), None);
+        let invalid_category = Config::load(&root.join("a"), Some(&root.join("d/warloc.toml")));
+
+        assert_eq!(
+            config,
+            Config {
+                exclude_glob: vec!["vendor/".into()],
+                hidden: Some(false),
+                test_attribute: vec!["ws::test".into(), "a::test".into()],
+                category_rule: vec![
+                    "src/testing/**=main".parse().unwrap(),
+                    "**/testing/**=tests".parse().unwrap(),
+                ],
+                threshold: vec!["main=100".parse().unwrap(), "main=200".parse().unwrap()],
+                output_format: Some(OutputFormat::Json),
+                ..Default::default()
+            }
+        );
+        assert!(explicit
+            .unwrap_err()
+            .to_string()
+            .contains("unknown variant `xml`"));
+        assert!(misspelled
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field `hiden`"));
+        assert!(invalid_category
+            .unwrap_err()
+            .to_string()
+            .contains("invalid category `test data`"));
+    }
+
+    #[test]
+    fn skip_manifests_above_workspace() {
+        let tree = TempTree::new("config-outer");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "not a manifest [\n");
+        tree.write(
+            "ws/Cargo.toml",
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.metadata.warloc]\nhidden = true\n",
+        );
+        tree.write("ws/a/Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write(
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\n\n[package.metadata.warloc]\nhidden = false\n",
+        );
+
+        let member = Config::load(&root.join("ws/a"), None).unwrap();
+        let single = Config::load(&root.join("b"), None).unwrap();
+        let outer = Config::load(root, None);
+
+        assert_eq!(member.hidden, Some(true));
+        assert_eq!(single.hidden, Some(false));
+        assert!(matches!(outer, Err(ConfigError::Parse(..))));
+    }
+
+    #[test]
+    fn negated_flags() {
+        let config = Config {
+            no_ignore: Some(true),
+            hidden: Some(true),
+            module_tree: Some(true),
+            ..Default::default()
+        };
+        let CargoCli::Command(mut args) = CargoCli::parse_from([
+            "cargo",
+            "warloc",
+            "--ignore",
+            "--no-hidden",
+            "--hidden",
+            "--module-tree",
+            "--no-module-tree",
+        ]);
+        config.apply(&mut args);
+
+        assert!(!args.no_ignore);
+        assert!(args.hidden);
+        assert!(!args.module_tree);
+    }
+}
@@ -0,0 +1,251 @@
+//! Locked dependencies and their sources on the local file system.
+//!
+//! Sources of the packages listed in `Cargo.lock` are looked up without network access: in the
+//! `vendor` directory next to the lock file, in the registry source cache of Cargo
+//! (`$CARGO_HOME/registry/src`) and in its git checkouts (`$CARGO_HOME/git/checkouts`).
+
+use std::{
+    env,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::manifest::find_packages;
+
+/// Package from `Cargo.lock` that is not a member of the workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    /// Source id, like `registry+https://github.com/rust-lang/crates.io-index`.
+    pub source: String,
+}
+
+#[derive(Debug)]
+pub enum LockfileError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl Display for LockfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "failed to parse {}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LockfileError {}
+
+#[derive(Debug, Deserialize)]
+struct RawLockfile {
+    #[serde(default)]
+    package: Vec<RawLockedPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawVersionManifest {
+    package: Option<RawVersionPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawVersionPackage {
+    version: Option<toml::Value>,
+}
+
+impl Dependency {
+    /// Reads the dependencies from a `Cargo.lock` file. Packages without a source are members of
+    /// the workspace and are left out.
+    pub fn load_locked(lock_path: impl AsRef<Path>) -> Result<Vec<Self>, LockfileError> {
+        let lock_path = lock_path.as_ref();
+        let contents = fs::read_to_string(lock_path)
+            .map_err(|e| LockfileError::Io(lock_path.to_path_buf(), e))?;
+        let lockfile: RawLockfile = toml::from_str(&contents)
+            .map_err(|e| LockfileError::Parse(lock_path.to_path_buf(), e))?;
+
+        Ok(lockfile
+            .package
+            .into_iter()
+            .filter_map(|package| {
+                Some(Self {
+                    name: package.name,
+                    version: package.version,
+                    source: package.source?,
+                })
+            })
+            .collect())
+    }
+
+    /// Finds the directory with the sources of the dependency. `vendor_dir` is checked first,
+    /// then the caches of Cargo in `cargo_home`.
+    pub fn find_source(&self, vendor_dir: &Path, cargo_home: &Path) -> Option<PathBuf> {
+        self.find_vendored(vendor_dir).or_else(|| {
+            if self.source.starts_with("git+") {
+                self.find_git_checkout(cargo_home)
+            } else {
+                self.find_registry_source(cargo_home)
+            }
+        })
+    }
+
+    /// `cargo vendor` puts a package in a directory named after the package, or after the
+    /// package and its version if several versions are vendored.
+    fn find_vendored(&self, vendor_dir: &Path) -> Option<PathBuf> {
+        let versioned = vendor_dir.join(format!("{}-{}", self.name, self.version));
+        if versioned.join("Cargo.toml").is_file() {
+            return Some(versioned);
+        }
+
+        let dir = vendor_dir.join(&self.name);
+        (manifest_version(&dir).as_deref() == Some(self.version.as_str())).then_some(dir)
+    }
+
+    /// Registry sources are unpacked to `registry/src/<index>/<name>-<version>`.
+    fn find_registry_source(&self, cargo_home: &Path) -> Option<PathBuf> {
+        let indexes = cargo_home.join("registry/src").read_dir().ok()?;
+        indexes.filter_map(|entry| entry.ok()).find_map(|entry| {
+            let dir = entry.path().join(format!("{}-{}", self.name, self.version));
+            dir.join("Cargo.toml").is_file().then_some(dir)
+        })
+    }
+
+    /// Git repositories are checked out to `git/checkouts/<repository>-<hash>/<short commit>`.
+    /// The package can be anywhere in the repository.
+    fn find_git_checkout(&self, cargo_home: &Path) -> Option<PathBuf> {
+        let (_, commit) = self.source.rsplit_once('#')?;
+        let short_commit = commit.get(..7)?;
+
+        let repositories = cargo_home.join("git/checkouts").read_dir().ok()?;
+        repositories
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(short_commit))
+            .filter(|checkout| checkout.is_dir())
+            .find_map(|checkout| {
+                find_packages(&checkout)
+                    .into_iter()
+                    .find(|package| package.name == self.name)
+                    .map(|package| package.dir)
+            })
+    }
+}
+
+impl Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Returns the home directory of Cargo: `$CARGO_HOME` or `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".cargo")))
+}
+
+/// Reads the version of the package in `dir` from its manifest.
+fn manifest_version(dir: &Path) -> Option<String> {
+    let contents = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    let manifest: RawVersionManifest = toml::from_str(&contents).ok()?;
+    match manifest.package?.version? {
+        toml::Value::String(version) => Some(version),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    #[test]
+    fn locked_dependency_sources() {
+        let tree = TempTree::new("deps");
+        let root = tree.path();
+
+        tree.write(
+            "project/Cargo.lock",
+            r#"
+version = 4
+
+[[package]]
+name = "project"
+version = "0.1.0"
+
+[[package]]
+name = "from-registry"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "from-git"
+version = "0.2.0"
+source = "git+https://github.com/a/b?branch=main#0123456789abcdef"
+
+[[package]]
+name = "vendored"
+version = "0.3.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "missing"
+version = "0.4.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        );
+        tree.write(
+            "home/registry/src/index.crates.io-1949cf8c6b5b557f/from-registry-1.0.0/Cargo.toml",
+            "[package]\nname = \"from-registry\"\nversion = \"1.0.0\"\n",
+        );
+        tree.write(
+            "home/git/checkouts/b-1234/0123456/crates/from-git/Cargo.toml",
+            "[package]\nname = \"from-git\"\nversion = \"0.2.0\"\n",
+        );
+        tree.write(
+            "project/vendor/vendored/Cargo.toml",
+            "[package]\nname = \"vendored\"\nversion = \"0.3.0\"\n",
+        );
+
+        let dependencies = Dependency::load_locked(root.join("project/Cargo.lock")).unwrap();
+        let sources: Vec<(String, Option<PathBuf>)> = dependencies
+            .iter()
+            .map(|dependency| {
+                let source =
+                    dependency.find_source(&root.join("project/vendor"), &root.join("home"));
+                (
+                    dependency.name.clone(),
+                    source.map(|dir| dir.strip_prefix(root).unwrap().to_path_buf()),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            sources,
+            vec![
+                (
+                    "from-registry".into(),
+                    Some(
+                        "home/registry/src/index.crates.io-1949cf8c6b5b557f/from-registry-1.0.0"
+                            .into()
+                    )
+                ),
+                (
+                    "from-git".into(),
+                    Some("home/git/checkouts/b-1234/0123456/crates/from-git".into())
+                ),
+                ("vendored".into(), Some("project/vendor/vendored".into())),
+                ("missing".into(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Stats of files summed up the directory tree.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::warlocs::{FileStats, Warlocs};
+
+/// Totals of the files under a directory, with the totals of its subdirectories.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DirTree {
+    pub path: PathBuf,
+    pub file_count: u64,
+    #[serde(flatten)]
+    pub stats: Warlocs,
+    /// Subdirectories containing counted files, sorted by name.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DirTree>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    file_count: u64,
+    stats: Warlocs,
+    children: BTreeMap<OsString, Node>,
+}
+
+impl DirTree {
+    /// Sums the stats of the files for each directory, starting from the innermost directory
+    /// containing all files. Directories deeper than `max_depth` levels below it are summed into
+    /// their ancestor at `max_depth`.
+    pub fn build(files: &BTreeMap<PathBuf, FileStats>, max_depth: Option<usize>) -> Self {
+        let root = common_dir(
+            files
+                .keys()
+                .map(|path| path.parent().unwrap_or(Path::new(""))),
+        );
+
+        let mut tree = Node::default();
+        for (path, file_stats) in files {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let components = dir.strip_prefix(&root).unwrap_or(dir).components();
+
+            let mut node = &mut tree;
+            node.add(&file_stats.stats);
+            for component in components.take(max_depth.unwrap_or(usize::MAX)) {
+                node = node
+                    .children
+                    .entry(component.as_os_str().to_os_string())
+                    .or_default();
+                node.add(&file_stats.stats);
+            }
+        }
+
+        let root = if root.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            root
+        };
+        tree.into_tree(root)
+    }
+
+    /// Returns the directory and all its subdirectories, each with its depth below this
+    /// directory, in depth-first order.
+    pub fn flatten(&self) -> Vec<(usize, &DirTree)> {
+        let mut dirs = vec![];
+        let mut stack = vec![(0, self)];
+        while let Some((depth, dir)) = stack.pop() {
+            dirs.push((depth, dir));
+            stack.extend(dir.children.iter().rev().map(|child| (depth + 1, child)));
+        }
+
+        dirs
+    }
+}
+
+impl Node {
+    fn add(&mut self, stats: &Warlocs) {
+        self.file_count += 1;
+        self.stats += stats;
+    }
+
+    fn into_tree(self, path: PathBuf) -> DirTree {
+        DirTree {
+            children: self
+                .children
+                .into_iter()
+                .map(|(name, child)| child.into_tree(path.join(name)))
+                .collect(),
+            path,
+            file_count: self.file_count,
+            stats: self.stats,
+        }
+    }
+}
+
+/// Returns the longest common ancestor of the directories.
+fn common_dir<'a>(dirs: impl IntoIterator<Item = &'a Path>) -> PathBuf {
+    let mut dirs = dirs.into_iter();
+    let Some(first) = dirs.next() else {
+        return PathBuf::new();
+    };
+
+    dirs.fold(first.to_path_buf(), |common, dir| {
+        common
+            .components()
+            .zip(dir.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Category;
+
+    use super::*;
+
+    fn file(code: u64) -> FileStats {
+        let mut stats = Warlocs::default();
+        stats.get_mut(Category::Main).code = code;
+        FileStats {
+            stats,
+            generated: None,
+        }
+    }
+
+    #[test]
+    fn sums_up_the_tree() {
+        let files = [
+            ("./src/lib.rs", 1),
+            ("./src/a/mod.rs", 2),
+            ("./src/a/b/c.rs", 4),
+            ("./tests/it.rs", 8),
+        ]
+        .into_iter()
+        .map(|(path, code)| (PathBuf::from(path), file(code)))
+        .collect();
+
+        let summary = |tree: &DirTree| -> Vec<(usize, PathBuf, u64, u64)> {
+            tree.flatten()
+                .into_iter()
+                .map(|(depth, dir)| (depth, dir.path.clone(), dir.file_count, dir.stats.code()))
+                .collect()
+        };
+
+        assert_eq!(
+            summary(&DirTree::build(&files, None)),
+            vec![
+                (0, ".".into(), 4, 15),
+                (1, "./src".into(), 3, 7),
+                (2, "./src/a".into(), 2, 6),
+                (3, "./src/a/b".into(), 1, 4),
+                (1, "./tests".into(), 1, 8),
+            ]
+        );
+        assert_eq!(
+            summary(&DirTree::build(&files, Some(1))),
+            vec![
+                (0, ".".into(), 4, 15),
+                (1, "./src".into(), 3, 7),
+                (1, "./tests".into(), 1, 8),
+            ]
+        );
+    }
+}
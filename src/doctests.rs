@@ -0,0 +1,139 @@
+//! Detection of doctests in documentation comments.
+//!
+//! Rustdoc compiles fenced code blocks of documentation as tests unless they are marked as
+//! `ignore` or written in another language. Lines of such blocks, including the fences, are
+//! counted as doctests instead of documentation.
+
+/// Tracks fenced code blocks across the lines of documentation comments.
+#[derive(Debug, Default, Clone)]
+pub struct DocState {
+    fence: Option<Fence>,
+}
+
+#[derive(Debug, Clone)]
+struct Fence {
+    marker: char,
+    len: usize,
+    is_doctest: bool,
+}
+
+impl DocState {
+    /// Processes a line of documentation with the comment markers stripped. Returns true if the
+    /// line belongs to a doctest.
+    pub fn is_doctest_line(&mut self, line: &str) -> bool {
+        let line = line.trim_start();
+        let marker_len = |marker: char| line.chars().take_while(|c| *c == marker).count();
+
+        match &self.fence {
+            Some(fence) => {
+                let is_doctest = fence.is_doctest;
+                let len = marker_len(fence.marker);
+                if len >= fence.len && line[len..].trim().is_empty() {
+                    self.fence = None;
+                }
+
+                is_doctest
+            }
+            None => {
+                let Some(marker) = ['`', '~'].into_iter().find(|m| marker_len(*m) >= 3) else {
+                    return false;
+                };
+
+                let len = marker_len(marker);
+                let is_doctest = is_doctest(&line[len..]);
+                self.fence = Some(Fence {
+                    marker,
+                    len,
+                    is_doctest,
+                });
+
+                is_doctest
+            }
+        }
+    }
+
+    /// Forgets about any open code block, e.g. when the documentation comment ends.
+    pub fn reset(&mut self) {
+        self.fence = None;
+    }
+}
+
+/// Returns true if rustdoc tests a code block with the given info string (the text after the
+/// opening fence).
+///
+/// Blocks without an info string or with only rustdoc attributes (`no_run`, `should_panic`,
+/// `compile_fail`, `edition2021`, ...) are Rust code. Any other word names a different language,
+/// unless `rust` is given explicitly. Blocks marked as `ignore` are not tested.
+fn is_doctest(info: &str) -> bool {
+    let mut seen_rust = false;
+    let mut seen_other = false;
+
+    let tokens = info
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        match token {
+            "ignore" => return false,
+            "rust" | "should_panic" | "no_run" | "compile_fail" | "test_harness"
+            | "standalone_crate" => seen_rust = true,
+            token if token.starts_with("edition") || token.starts_with("ignore-") => {
+                seen_rust = true
+            }
+            token if is_error_code(token) => seen_rust = true,
+            _ => seen_other = true,
+        }
+    }
+
+    seen_rust || !seen_other
+}
+
+/// Error codes like `E0308` can be given for `compile_fail` doctests.
+fn is_error_code(token: &str) -> bool {
+    token.len() == 5 && token.starts_with('E') && token[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doctest_lines(doc: &str) -> Vec<bool> {
+        let mut state = DocState::default();
+        doc.lines()
+            .map(|line| state.is_doctest_line(line))
+            .collect()
+    }
+
+    #[test]
+    fn rust_code_blocks() {
+        let doc = "Prose\n```\nlet a = 1;\n```\nMore prose\n``` no_run\nrun();\n```\n";
+
+        assert_eq!(
+            doctest_lines(doc),
+            vec![false, true, true, true, false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn other_code_blocks() {
+        let doc = "```text\n```\n```ignore\n```\n~~~toml\n```\n~~~\n````sh,rust\n````\n";
+
+        assert_eq!(
+            doctest_lines(doc),
+            vec![false, false, false, false, false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn info_strings() {
+        assert!(is_doctest(""));
+        assert!(is_doctest("rust"));
+        assert!(is_doctest("compile_fail,E0308"));
+        assert!(is_doctest("should_panic, edition2021"));
+        assert!(is_doctest("text,rust"));
+        assert!(!is_doctest("text"));
+        assert!(!is_doctest("rust,ignore"));
+    }
+}
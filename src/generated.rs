@@ -0,0 +1,176 @@
+//! Detection of generated code.
+//!
+//! Code generators mark their output with a comment at the top of the file, like
+//! `// This file is @generated by prost-build.` or
+//! `/* automatically generated by rust-bindgen 0.69.4 */`. Code generated at build time is pulled
+//! into a crate with `include!(concat!(env!("OUT_DIR"), "/file.rs"))`.
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::lexer::{Token, TokenKind};
+
+/// Reason for treating code as generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratedReason {
+    /// `@generated` marker in the file header.
+    Marker,
+    /// Header comment like `// This file is automatically generated`.
+    Header,
+    /// Output of `prost-build`.
+    Prost,
+    /// Output of `tonic-build`: the output of `prost-build` with gRPC clients and servers using
+    /// `tonic`.
+    Tonic,
+    /// Output of `bindgen`.
+    Bindgen,
+    /// `include!` of a file from `OUT_DIR`. Only the lines of the macro call are generated.
+    OutDirInclude,
+}
+
+impl GeneratedReason {
+    /// Returns true if the reason marks the whole file as generated rather than a region of it.
+    pub fn is_whole_file(&self) -> bool {
+        *self != Self::OutDirInclude
+    }
+
+    /// Detects a generated file by a comment in its header: an `@generated` marker, a comment
+    /// starting with a phrase like "This file is automatically generated", or a Go-style
+    /// "Code generated by ... DO NOT EDIT." line. Mentions of generated code further into a
+    /// comment, e.g. "Maps auto-generated IDs", do not count.
+    pub fn from_header_comment(comment: &str) -> Option<Self> {
+        let comment = comment.to_lowercase();
+        let text = comment
+            .trim_start_matches(['/', '*', '!'])
+            .trim_start()
+            .trim_start_matches(['*', '!'])
+            .trim_start();
+        let text = [
+            "this file is ",
+            "this file was ",
+            "this code is ",
+            "this code was ",
+        ]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text);
+
+        let is_generated = comment.contains("@generated")
+            || [
+                "automatically generated",
+                "auto-generated",
+                "autogenerated",
+                "generated by",
+            ]
+            .iter()
+            .any(|phrase| text.starts_with(phrase))
+            || (text.starts_with("code generated") && text.contains("do not edit"));
+        if !is_generated {
+            return None;
+        }
+
+        if comment.contains("rust-bindgen") {
+            Some(Self::Bindgen)
+        } else if comment.contains("tonic-build") {
+            Some(Self::Tonic)
+        } else if comment.contains("prost-build") {
+            Some(Self::Prost)
+        } else if comment.contains("@generated") {
+            Some(Self::Marker)
+        } else {
+            Some(Self::Header)
+        }
+    }
+}
+
+impl Display for GeneratedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Marker => "@generated marker",
+            Self::Header => "automatically generated header",
+            Self::Prost => "prost output",
+            Self::Tonic => "tonic output",
+            Self::Bindgen => "bindgen output",
+            Self::OutDirInclude => "include! from OUT_DIR",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Returns true if the arguments of an `include!` macro refer to `OUT_DIR`, e.g.
+/// `concat!(env!("OUT_DIR"), "/file.rs")`. Trivia tokens must be filtered out beforehand.
+pub fn includes_out_dir(args: &[Token]) -> bool {
+    args.windows(4).any(|window| match window {
+        [env, bang, open, value] => {
+            env.kind == TokenKind::Ident
+                && env.text == "env"
+                && bang.kind == TokenKind::Punct('!')
+                && matches!(open.kind, TokenKind::Punct('(' | '[' | '{'))
+                && value.string_value() == Some("OUT_DIR")
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    #[test]
+    fn header_comments() {
+        let cases = [
+            (
+                "// This file is @generated by prost-build.",
+                Some(GeneratedReason::Prost),
+            ),
+            (
+                "/* automatically generated by rust-bindgen 0.69.4 */",
+                Some(GeneratedReason::Bindgen),
+            ),
+            ("// @generated", Some(GeneratedReason::Marker)),
+            (
+                "// This file is automatically generated. Do not edit.",
+                Some(GeneratedReason::Header),
+            ),
+            ("// Auto-generated bindings", Some(GeneratedReason::Header)),
+            (
+                "// Code generated by protoc-gen-rust. DO NOT EDIT.",
+                Some(GeneratedReason::Header),
+            ),
+            ("// Copyright 2024 The Authors", None),
+            ("// Maps auto-generated IDs to users", None),
+            ("/* Wraps the output of prost-build */", None),
+        ];
+
+        for (comment, reason) in cases {
+            assert_eq!(
+                GeneratedReason::from_header_comment(comment),
+                reason,
+                "{comment}"
+            );
+        }
+    }
+
+    #[test]
+    fn out_dir_includes() {
+        let args = |source: &str| -> Vec<Token> {
+            Lexer::new(source.as_bytes())
+                .filter(|token| !token.kind.is_trivia())
+                .collect()
+        };
+
+        assert!(includes_out_dir(&args(
+            r#"concat!(env!("OUT_DIR"), "/proto.rs")"#
+        )));
+        assert!(includes_out_dir(&args(
+            r#"concat!(env! ( "OUT_DIR" ), "/proto.rs")"#
+        )));
+        assert!(!includes_out_dir(&args(r#""proto.rs""#)));
+        assert!(!includes_out_dir(&args(
+            r#"concat!(env!("CARGO_MANIFEST_DIR"), "/proto.rs")"#
+        )));
+    }
+}
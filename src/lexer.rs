@@ -0,0 +1,743 @@
+//! Lexical scanner for Rust source code.
+//!
+//! The scanner follows the lexical grammar of the Rust reference: comments (including nested
+//! block comments and doc comments), identifiers, raw identifiers, lifetimes, all literal kinds
+//! with their prefixes, escapes and suffixes, punctuation and a leading shebang line. It does not
+//! validate the source, so malformed input still produces a token stream that covers every
+//! character.
+//!
+//! Every token keeps its source text. Block comments and string literals may span several
+//! lines, in which case the text contains the line breaks.
+
+use std::{
+    collections::VecDeque,
+    io::{BufReader, Read},
+    mem,
+};
+
+use utf8_chars::BufReadCharsExt;
+
+/// A single lexical token together with its source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `\n` or `\r\n`.
+    LineBreak,
+    /// Any whitespace not containing a line break.
+    WhiteSpace,
+    /// `#!/usr/bin/env run-cargo-script` at the very beginning of a file.
+    Shebang,
+    /// `// comment`, `/// outer doc` or `//! inner doc`.
+    LineComment {
+        doc_style: Option<DocStyle>,
+    },
+    /// `/* comment */`, `/** outer doc */` or `/*! inner doc */`. Block comments can be nested.
+    BlockComment {
+        doc_style: Option<DocStyle>,
+        terminated: bool,
+    },
+    /// Identifier or keyword.
+    Ident,
+    /// `r#ident`.
+    RawIdent,
+    /// `'a` or `'r#a`.
+    Lifetime,
+    Literal {
+        kind: LiteralKind,
+        terminated: bool,
+    },
+    /// Single punctuation character, e.g. `{`, `#` or `;`.
+    Punct(char),
+    /// Any character that cannot start a token.
+    Unknown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocStyle {
+    /// `///` or `/**`.
+    Outer,
+    /// `//!` or `/*!`.
+    Inner,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LiteralKind {
+    /// `'a'`
+    Char,
+    /// `b'a'`
+    Byte,
+    /// `"abc"`
+    Str,
+    /// `b"abc"`
+    ByteStr,
+    /// `c"abc"`
+    CStr,
+    /// `r"abc"`, `r#"abc"#`
+    RawStr { hashes: usize },
+    /// `br"abc"`, `br#"abc"#`
+    RawByteStr { hashes: usize },
+    /// `cr"abc"`, `cr#"abc"#`
+    RawCStr { hashes: usize },
+    /// `12`, `0x1f`, `1_000u64`
+    Int,
+    /// `1.0`, `1e10`, `2.5f32`
+    Float,
+}
+
+impl Token {
+    /// Returns the contents of a terminated string literal without the prefix, quotes and
+    /// hashes. Escape sequences are kept as they are.
+    pub fn string_value(&self) -> Option<&str> {
+        let TokenKind::Literal {
+            kind,
+            terminated: true,
+        } = self.kind
+        else {
+            return None;
+        };
+
+        if !matches!(
+            kind,
+            LiteralKind::Str
+                | LiteralKind::ByteStr
+                | LiteralKind::CStr
+                | LiteralKind::RawStr { .. }
+                | LiteralKind::RawByteStr { .. }
+                | LiteralKind::RawCStr { .. }
+        ) {
+            return None;
+        }
+
+        let start = self.text.find('"')? + 1;
+        let end = self.text.rfind('"')?;
+
+        self.text.get(start..end)
+    }
+}
+
+impl TokenKind {
+    /// Returns true for tokens that carry no meaning for the compiler: whitespace, line breaks,
+    /// comments and the shebang line.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Self::LineBreak
+                | Self::WhiteSpace
+                | Self::Shebang
+                | Self::LineComment { .. }
+                | Self::BlockComment { .. }
+        )
+    }
+}
+
+pub struct Lexer<T: Read> {
+    reader: BufReader<T>,
+    lookahead: VecDeque<char>,
+    at_file_start: bool,
+}
+
+impl<T: Read> Lexer<T> {
+    pub fn new(reader: T) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            lookahead: VecDeque::new(),
+            at_file_start: true,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        let first = self.peek(0)?;
+        let mut text = String::new();
+
+        if mem::take(&mut self.at_file_start) && self.is_shebang() {
+            self.eat_while(&mut text, |c| c != '\n');
+            return Some(Token {
+                kind: TokenKind::Shebang,
+                text,
+            });
+        }
+
+        let kind = match first {
+            '\n' => {
+                self.bump(&mut text);
+                TokenKind::LineBreak
+            }
+            '\r' if self.peek(1) == Some('\n') => {
+                self.bump(&mut text);
+                self.bump(&mut text);
+                TokenKind::LineBreak
+            }
+            c if is_whitespace(c) => {
+                self.eat_while(&mut text, |c| is_whitespace(c) && c != '\n');
+                TokenKind::WhiteSpace
+            }
+            '/' if self.peek(1) == Some('/') => self.line_comment(&mut text),
+            '/' if self.peek(1) == Some('*') => self.block_comment(&mut text),
+            'r' => match self.raw_string_hashes(1) {
+                Some(_) => self.raw_string(&mut text, 1, |hashes| LiteralKind::RawStr { hashes }),
+                None if self.peek(1) == Some('#') && self.peek(2).is_some_and(is_id_start) => {
+                    self.bump(&mut text);
+                    self.bump(&mut text);
+                    self.eat_while(&mut text, is_id_continue);
+                    TokenKind::RawIdent
+                }
+                None => self.ident(&mut text),
+            },
+            'b' => match self.peek(1) {
+                Some('\'') => {
+                    self.bump(&mut text);
+                    self.quoted(&mut text, LiteralKind::Byte)
+                }
+                Some('"') => {
+                    self.bump(&mut text);
+                    self.double_quoted(&mut text, LiteralKind::ByteStr)
+                }
+                Some('r') if self.raw_string_hashes(2).is_some() => {
+                    self.raw_string(&mut text, 2, |hashes| LiteralKind::RawByteStr { hashes })
+                }
+                _ => self.ident(&mut text),
+            },
+            'c' => match self.peek(1) {
+                Some('"') => {
+                    self.bump(&mut text);
+                    self.double_quoted(&mut text, LiteralKind::CStr)
+                }
+                Some('r') if self.raw_string_hashes(2).is_some() => {
+                    self.raw_string(&mut text, 2, |hashes| LiteralKind::RawCStr { hashes })
+                }
+                _ => self.ident(&mut text),
+            },
+            c if is_id_start(c) => self.ident(&mut text),
+            c if c.is_ascii_digit() => self.number(&mut text),
+            '\'' => self.lifetime_or_char(&mut text),
+            '"' => self.double_quoted(&mut text, LiteralKind::Str),
+            c => {
+                self.bump(&mut text);
+                if c.is_ascii_punctuation() {
+                    TokenKind::Punct(c)
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+        };
+
+        Some(Token { kind, text })
+    }
+
+    /// A file starting with `#!` has a shebang line, unless it is actually an inner attribute
+    /// like `#![allow(unused)]`.
+    fn is_shebang(&mut self) -> bool {
+        if self.peek(0) != Some('#') || self.peek(1) != Some('!') {
+            return false;
+        }
+
+        let mut n = 2;
+        while let Some(c) = self.peek(n) {
+            if !is_whitespace(c) {
+                return c != '[';
+            }
+            n += 1;
+        }
+
+        true
+    }
+
+    fn line_comment(&mut self, text: &mut String) -> TokenKind {
+        self.bump(text);
+        self.bump(text);
+
+        let doc_style = match (self.peek(0), self.peek(1)) {
+            (Some('!'), _) => Some(DocStyle::Inner),
+            (Some('/'), next) if next != Some('/') => Some(DocStyle::Outer),
+            _ => None,
+        };
+
+        self.eat_while(text, |c| c != '\n');
+        if text.ends_with('\r') {
+            text.pop();
+            self.lookahead.push_front('\r');
+        }
+
+        TokenKind::LineComment { doc_style }
+    }
+
+    fn block_comment(&mut self, text: &mut String) -> TokenKind {
+        self.bump(text);
+        self.bump(text);
+
+        let doc_style = match (self.peek(0), self.peek(1)) {
+            (Some('!'), _) => Some(DocStyle::Inner),
+            (Some('*'), Some(next)) if next != '*' && next != '/' => Some(DocStyle::Outer),
+            _ => None,
+        };
+
+        let mut depth = 1usize;
+        while let Some(c) = self.bump(text) {
+            match c {
+                '/' if self.peek(0) == Some('*') => {
+                    self.bump(text);
+                    depth += 1;
+                }
+                '*' if self.peek(0) == Some('/') => {
+                    self.bump(text);
+                    depth -= 1;
+                    if depth == 0 {
+                        return TokenKind::BlockComment {
+                            doc_style,
+                            terminated: true,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        TokenKind::BlockComment {
+            doc_style,
+            terminated: false,
+        }
+    }
+
+    fn ident(&mut self, text: &mut String) -> TokenKind {
+        self.bump(text);
+        self.eat_while(text, is_id_continue);
+        TokenKind::Ident
+    }
+
+    fn number(&mut self, text: &mut String) -> TokenKind {
+        let first = self.bump(text);
+
+        if first == Some('0') {
+            match self.peek(0) {
+                Some('b' | 'o') => {
+                    self.bump(text);
+                    self.eat_while(text, |c| c.is_ascii_digit() || c == '_');
+                    return self.literal_with_suffix(text, LiteralKind::Int, true);
+                }
+                Some('x') => {
+                    self.bump(text);
+                    self.eat_while(text, |c| c.is_ascii_hexdigit() || c == '_');
+                    return self.literal_with_suffix(text, LiteralKind::Int, true);
+                }
+                _ => {}
+            }
+        }
+
+        self.eat_while(text, |c| c.is_ascii_digit() || c == '_');
+
+        let mut kind = LiteralKind::Int;
+        match (self.peek(0), self.peek(1)) {
+            // `1.0`, `1.` but not `1..2` or `1.max(2)`
+            (Some('.'), next) if next != Some('.') && !next.is_some_and(is_id_start) => {
+                self.bump(text);
+                kind = LiteralKind::Float;
+                if self.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                    self.eat_while(text, |c| c.is_ascii_digit() || c == '_');
+                    self.exponent(text);
+                }
+            }
+            (Some('e' | 'E'), _) if self.exponent(text) => kind = LiteralKind::Float,
+            _ => {}
+        }
+
+        self.literal_with_suffix(text, kind, true)
+    }
+
+    /// Consumes `e10`, `E+3` or `e-1_0` if the exponent is complete.
+    fn exponent(&mut self, text: &mut String) -> bool {
+        if !matches!(self.peek(0), Some('e' | 'E')) {
+            return false;
+        }
+
+        let digits_at = if matches!(self.peek(1), Some('+' | '-')) {
+            2
+        } else {
+            1
+        };
+        let mut n = digits_at;
+        while self.peek(n) == Some('_') {
+            n += 1;
+        }
+        if !self.peek(n).is_some_and(|c| c.is_ascii_digit()) {
+            return false;
+        }
+
+        for _ in 0..digits_at {
+            self.bump(text);
+        }
+        self.eat_while(text, |c| c.is_ascii_digit() || c == '_');
+        true
+    }
+
+    fn lifetime_or_char(&mut self, text: &mut String) -> TokenKind {
+        let can_be_lifetime = self.peek(2) != Some('\'')
+            && self
+                .peek(1)
+                .is_some_and(|c| is_id_start(c) || c.is_ascii_digit());
+
+        if !can_be_lifetime {
+            return self.quoted(text, LiteralKind::Char);
+        }
+
+        self.bump(text);
+        if self.peek(0) == Some('r')
+            && self.peek(1) == Some('#')
+            && self.peek(2).is_some_and(is_id_start)
+        {
+            self.bump(text);
+            self.bump(text);
+        }
+        self.eat_while(text, is_id_continue);
+
+        if self.peek(0) == Some('\'') {
+            self.bump(text);
+            self.literal_with_suffix(text, LiteralKind::Char, true)
+        } else {
+            TokenKind::Lifetime
+        }
+    }
+
+    /// Char or byte literal starting at the opening `'`.
+    fn quoted(&mut self, text: &mut String, kind: LiteralKind) -> TokenKind {
+        self.bump(text);
+
+        if self.peek(1) == Some('\'') && self.peek(0) != Some('\\') {
+            self.bump(text);
+            self.bump(text);
+            return self.literal_with_suffix(text, kind, true);
+        }
+
+        let terminated = loop {
+            match self.peek(0) {
+                Some('\'') => {
+                    self.bump(text);
+                    break true;
+                }
+                // Most likely the start of a comment after an unterminated literal
+                Some('/') => break false,
+                Some('\n') if self.peek(1) != Some('\'') => break false,
+                Some('\\') => {
+                    self.bump(text);
+                    self.bump(text);
+                }
+                Some(_) => {
+                    self.bump(text);
+                }
+                None => break false,
+            }
+        };
+
+        self.literal_with_suffix(text, kind, terminated)
+    }
+
+    /// String literal starting at the opening `"`.
+    fn double_quoted(&mut self, text: &mut String, kind: LiteralKind) -> TokenKind {
+        self.bump(text);
+
+        let terminated = loop {
+            match self.bump(text) {
+                Some('"') => break true,
+                Some('\\') => {
+                    self.bump(text);
+                }
+                Some(_) => {}
+                None => break false,
+            }
+        };
+
+        self.literal_with_suffix(text, kind, terminated)
+    }
+
+    /// Returns the number of `#` characters if a raw string opening quote follows `offset` or
+    /// more hashes.
+    fn raw_string_hashes(&mut self, offset: usize) -> Option<usize> {
+        let mut n = offset;
+        while self.peek(n) == Some('#') {
+            n += 1;
+        }
+
+        (self.peek(n) == Some('"')).then_some(n - offset)
+    }
+
+    fn raw_string(
+        &mut self,
+        text: &mut String,
+        prefix_len: usize,
+        kind: impl FnOnce(usize) -> LiteralKind,
+    ) -> TokenKind {
+        let hashes = self
+            .raw_string_hashes(prefix_len)
+            .expect("raw string start is checked by caller");
+
+        for _ in 0..prefix_len + hashes + 1 {
+            self.bump(text);
+        }
+
+        let terminated = loop {
+            match self.bump(text) {
+                Some('"') if (0..hashes).all(|n| self.peek(n) == Some('#')) => {
+                    for _ in 0..hashes {
+                        self.bump(text);
+                    }
+                    break true;
+                }
+                Some(_) => {}
+                None => break false,
+            }
+        };
+
+        self.literal_with_suffix(text, kind(hashes), terminated)
+    }
+
+    fn literal_with_suffix(
+        &mut self,
+        text: &mut String,
+        kind: LiteralKind,
+        terminated: bool,
+    ) -> TokenKind {
+        if terminated && self.peek(0).is_some_and(is_id_start) {
+            self.eat_while(text, is_id_continue);
+        }
+
+        TokenKind::Literal { kind, terminated }
+    }
+
+    fn eat_while(&mut self, text: &mut String, mut predicate: impl FnMut(char) -> bool) {
+        while let Some(c) = self.peek(0) {
+            if !predicate(c) {
+                break;
+            }
+            self.bump(text);
+        }
+    }
+
+    fn bump(&mut self, text: &mut String) -> Option<char> {
+        let c = match self.lookahead.pop_front() {
+            Some(c) => Some(c),
+            None => self.read_char(),
+        };
+
+        if let Some(c) = c {
+            text.push(c);
+        }
+        c
+    }
+
+    fn peek(&mut self, n: usize) -> Option<char> {
+        while self.lookahead.len() <= n {
+            let c = self.read_char()?;
+            self.lookahead.push_back(c);
+        }
+
+        self.lookahead.get(n).copied()
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        self.reader.read_char().ok().flatten()
+    }
+}
+
+impl<T: Read> Iterator for Lexer<T> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+/// Whitespace as defined by the Rust reference (`Pattern_White_Space`).
+fn is_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n'
+            | '\u{000B}'
+            | '\u{000C}'
+            | '\r'
+            | ' '
+            | '\u{0085}'
+            | '\u{200E}'
+            | '\u{200F}'
+            | '\u{2028}'
+            | '\u{2029}'
+    )
+}
+
+fn is_id_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_id_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        Lexer::new(source.as_bytes())
+            .map(|token| token.kind)
+            .filter(|kind| *kind != TokenKind::WhiteSpace)
+            .collect()
+    }
+
+    fn literal(kind: LiteralKind) -> TokenKind {
+        TokenKind::Literal {
+            kind,
+            terminated: true,
+        }
+    }
+
+    #[test]
+    fn tokens_cover_whole_source() {
+        let source = "#!/bin/sh\nfn main() { let c = '\"'; /* a /* b */ */ }\r\n";
+        let text: String = Lexer::new(source.as_bytes()).map(|t| t.text).collect();
+
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn char_literals() {
+        assert_eq!(
+            kinds(r#"'{' '"' '\'' '\\' '\u{7FFF}' 'ab'"#),
+            vec![literal(LiteralKind::Char); 6]
+        );
+    }
+
+    #[test]
+    fn lifetimes() {
+        assert_eq!(
+            kinds("&'a str 'static 'r#fn"),
+            vec![
+                TokenKind::Punct('&'),
+                TokenKind::Lifetime,
+                TokenKind::Ident,
+                TokenKind::Lifetime,
+                TokenKind::Lifetime,
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_literals() {
+        assert_eq!(
+            kinds(r#"b'{' b"\"{" br"}""#),
+            vec![
+                literal(LiteralKind::Byte),
+                literal(LiteralKind::ByteStr),
+                literal(LiteralKind::RawByteStr { hashes: 0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_literals() {
+        assert_eq!(
+            kinds(r####"r"\" r#"a"b"# r###"x"##y"### cr#"c"# c"\"" "a\"b" "s"suffix"####),
+            vec![
+                literal(LiteralKind::RawStr { hashes: 0 }),
+                literal(LiteralKind::RawStr { hashes: 1 }),
+                literal(LiteralKind::RawStr { hashes: 3 }),
+                literal(LiteralKind::RawCStr { hashes: 1 }),
+                literal(LiteralKind::CStr),
+                literal(LiteralKind::Str),
+                literal(LiteralKind::Str),
+            ]
+        );
+        assert_eq!(
+            kinds(r###"r#ident r##"unterminated"#"###),
+            vec![
+                TokenKind::RawIdent,
+                TokenKind::Literal {
+                    kind: LiteralKind::RawStr { hashes: 2 },
+                    terminated: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            kinds("1 1_000u64 0xff 1.5 1e10 2.5e-3f32 1. 1..2 1.max"),
+            vec![
+                literal(LiteralKind::Int),
+                literal(LiteralKind::Int),
+                literal(LiteralKind::Int),
+                literal(LiteralKind::Float),
+                literal(LiteralKind::Float),
+                literal(LiteralKind::Float),
+                literal(LiteralKind::Float),
+                literal(LiteralKind::Int),
+                TokenKind::Punct('.'),
+                TokenKind::Punct('.'),
+                literal(LiteralKind::Int),
+                literal(LiteralKind::Int),
+                TokenKind::Punct('.'),
+                TokenKind::Ident,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            kinds("// a\n/// b\n//! c\n//// d\n/** e */ /*! f */ /*** g */ /**/ /* /* h */ */"),
+            vec![
+                TokenKind::LineComment { doc_style: None },
+                TokenKind::LineBreak,
+                TokenKind::LineComment {
+                    doc_style: Some(DocStyle::Outer)
+                },
+                TokenKind::LineBreak,
+                TokenKind::LineComment {
+                    doc_style: Some(DocStyle::Inner)
+                },
+                TokenKind::LineBreak,
+                TokenKind::LineComment { doc_style: None },
+                TokenKind::LineBreak,
+                TokenKind::BlockComment {
+                    doc_style: Some(DocStyle::Outer),
+                    terminated: true
+                },
+                TokenKind::BlockComment {
+                    doc_style: Some(DocStyle::Inner),
+                    terminated: true
+                },
+                TokenKind::BlockComment {
+                    doc_style: None,
+                    terminated: true
+                },
+                TokenKind::BlockComment {
+                    doc_style: None,
+                    terminated: true
+                },
+                TokenKind::BlockComment {
+                    doc_style: None,
+                    terminated: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn shebang() {
+        assert_eq!(
+            kinds("#!/usr/bin/env rust-script\nfn"),
+            vec![TokenKind::Shebang, TokenKind::LineBreak, TokenKind::Ident]
+        );
+        assert_eq!(
+            kinds("#![allow(unused)]")[..3],
+            [
+                TokenKind::Punct('#'),
+                TokenKind::Punct('!'),
+                TokenKind::Punct('[')
+            ]
+        );
+    }
+}
@@ -0,0 +1,934 @@
+//! Wise analysis of Rust lines of code.
+//!
+//! Counts lines of Rust code, split into code, blank lines, documentation, doctests and comments,
+//! for main code, tests, examples, benchmarks, build scripts, generated code and user-defined
+//! categories. This is the library behind the `cargo warloc` command.
+//!
+//! ```
+//! use cargo_warloc::{count_str, Category, VisitorContext, VisitorOptions};
+//!
+//! let source = "/// Adds one.\nfn add_one(x: u32) -> u32 {\n    x + 1\n}\n";
+//! let file = count_str(source, VisitorContext::Main, &VisitorOptions::default());
+//!
+//! assert_eq!(file.stats[Category::Main].code, 3);
+//! assert_eq!(file.stats[Category::Main].docs, 1);
+//! ```
+
+mod archive;
+mod attribute;
+mod cfg;
+mod dependencies;
+mod dir_tree;
+mod doctests;
+mod generated;
+mod lexer;
+mod manifest;
+mod modules;
+mod packaged;
+mod rules;
+#[cfg(test)]
+mod test_utils;
+mod thresholds;
+mod visitor;
+mod warlocs;
+mod workspace;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, Read},
+    panic,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use ignore::{gitignore::GitignoreBuilder, overrides::OverrideBuilder, WalkBuilder, WalkState};
+use packaged::PublishedFiles;
+
+pub use archive::count_crate_archive;
+pub use attribute::TEST_ATTRIBUTES;
+pub use dependencies::{cargo_home, Dependency, LockfileError};
+pub use dir_tree::DirTree;
+pub use generated::GeneratedReason;
+pub use manifest::{find_packages, Package, Target, TargetKind};
+pub use modules::ModuleTree;
+pub use rules::{CategoryRule, CategoryRuleError, CategoryRules};
+pub use thresholds::{check_thresholds, Threshold, ThresholdError, ThresholdExceeded};
+pub use visitor::{Visitor, VisitorContext, VisitorOptions};
+pub use warlocs::{Category, FileStats, InvalidCategory, Locs, Warlocs};
+pub use workspace::{PackageSelection, Workspace, WorkspaceError};
+
+/// Settings for counting a directory.
+#[derive(Debug, Default, Clone)]
+pub struct CountOptions {
+    /// Settings controlling how lines are classified.
+    pub visitor: VisitorOptions,
+    /// Settings controlling which files are found in directories.
+    pub walk: WalkOptions,
+    /// Rules assigning files to categories by their path relative to their package, taking
+    /// precedence over the classification by Cargo targets and path conventions.
+    pub category_rules: CategoryRules,
+    /// Separates files that are not reachable from the crate roots of Cargo targets through
+    /// `mod` declarations into [`DirStats::unreachable`].
+    pub module_tree: bool,
+    /// Skips files marked as generated and does not count generated regions of other files.
+    pub exclude_generated: bool,
+    /// Counts only files published with their packages by `cargo package`, following the
+    /// `package.include` and `package.exclude` rules of the manifests. Files outside of packages
+    /// are not counted.
+    pub packaged: bool,
+    /// Number of threads searching directories and counting files. `0` uses one thread per
+    /// available core. Results do not depend on the number of threads.
+    pub jobs: usize,
+}
+
+impl CountOptions {
+    /// Number of threads to use, see [`CountOptions::jobs`].
+    fn jobs(&self) -> usize {
+        match self.jobs {
+            0 => thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            jobs => jobs,
+        }
+    }
+}
+
+/// Settings for finding Rust files in directories. Files given explicitly are always counted.
+#[derive(Debug, Default, Clone)]
+pub struct WalkOptions {
+    /// Glob patterns of the files to count, relative to the searched directory. All files are
+    /// counted if there are none.
+    pub include: Vec<String>,
+    /// Glob patterns of the files and directories to leave out, relative to the searched
+    /// directory.
+    pub exclude: Vec<String>,
+    /// Counts files ignored by `.gitignore`, `.ignore` and `.warlocignore` files.
+    pub no_ignore: bool,
+    /// Counts hidden files and searches hidden directories.
+    pub hidden: bool,
+    pub follow_symlinks: bool,
+}
+
+/// Stats of all Rust files under a directory.
+#[derive(Debug, Default, Clone)]
+pub struct DirStats {
+    /// Stats of each counted file.
+    pub files: BTreeMap<PathBuf, FileStats>,
+    /// Stats of files that are not part of any crate. Only set with
+    /// [`CountOptions::module_tree`].
+    pub unreachable: Option<BTreeMap<PathBuf, FileStats>>,
+}
+
+impl DirStats {
+    /// Sum of the stats of all counted files, not including unreachable files.
+    pub fn totals(&self) -> Warlocs {
+        self.files.values().map(|file| &file.stats).sum()
+    }
+}
+
+/// Stats of the files of a Cargo target.
+#[derive(Debug, Clone)]
+pub struct TargetStats {
+    /// Name of the package of the target.
+    pub package: String,
+    pub target: Target,
+    pub file_count: u64,
+    pub stats: Warlocs,
+}
+
+/// Stats of the locked dependencies of a workspace.
+#[derive(Debug, Default, Clone)]
+pub struct DependencyStats {
+    /// Stats of each dependency with sources available locally, in the order of `Cargo.lock`.
+    pub counted: Vec<(Dependency, DirStats)>,
+    /// Dependencies without sources available locally.
+    pub missing: Vec<Dependency>,
+}
+
+/// Counts lines of Rust source code. The code is assigned to the given context unless it is
+/// marked otherwise, e.g. with `#[cfg(test)]`.
+pub fn count_str(source: &str, context: VisitorContext, options: &VisitorOptions) -> FileStats {
+    count_reader(source.as_bytes(), context, options)
+}
+
+/// Counts lines of Rust source code read from `reader`. See [`count_str`].
+pub fn count_reader(
+    reader: impl Read,
+    context: VisitorContext,
+    options: &VisitorOptions,
+) -> FileStats {
+    Visitor::from_reader(reader, context, options).visit_file()
+}
+
+/// Counts lines of a Rust file. The context is determined from the path, e.g. files under
+/// `tests` directories are tests.
+pub fn count_file(path: impl AsRef<Path>, options: &VisitorOptions) -> io::Result<FileStats> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+
+    Ok(count_reader(
+        file,
+        VisitorContext::from_file_path(path),
+        options,
+    ))
+}
+
+/// Counts lines of all Rust files under `root` that are not ignored by `.gitignore`,
+/// `.warlocignore` and similar files, or left out by [`CountOptions::walk`]. Files of Cargo
+/// packages are assigned the context of the targets they belong to, also when `root` is a
+/// directory inside a package.
+pub fn count_dir(root: impl AsRef<Path>, options: &CountOptions) -> io::Result<DirStats> {
+    let root = root.as_ref();
+    let package_root = manifest_dir(root).unwrap_or_else(|| root.to_path_buf());
+    let module_tree = ModuleTree::build(&package_root, &options.visitor.test_attributes);
+
+    let mut published = PublishedFiles::default();
+    let paths = enumerate_rust_files(root, &options.walk, options.jobs())?
+        .into_iter()
+        .filter(|path| !options.packaged || published.contains(path))
+        .collect();
+    let files = count_in_parallel(paths, options.jobs(), |path| {
+        count_package_file(path, &module_tree, options)
+    })?;
+
+    Ok(DirStats::new(files, options, |path| {
+        module_tree.contains(path)
+    }))
+}
+
+/// Counts lines of the given files with the same rules as [`count_dir`]. Files inside a Cargo
+/// package are assigned the context of the targets they belong to.
+pub fn count_files(
+    paths: impl IntoIterator<Item = PathBuf>,
+    options: &CountOptions,
+) -> io::Result<DirStats> {
+    // Module trees of the packages the files belong to, by package directory
+    let mut module_trees: HashMap<PathBuf, ModuleTree> = HashMap::new();
+    let mut package_dirs = HashMap::new();
+    let mut published = PublishedFiles::default();
+
+    let mut counted_paths = vec![];
+    for path in paths {
+        if options.packaged && !published.contains(&path) {
+            continue;
+        }
+
+        let package_dir = package_dir(&path);
+        if let Some(dir) = &package_dir {
+            module_trees
+                .entry(dir.clone())
+                .or_insert_with(|| ModuleTree::build(dir, &options.visitor.test_attributes));
+        }
+        package_dirs.insert(path.clone(), package_dir);
+        counted_paths.push(path);
+    }
+
+    let no_package = ModuleTree::default();
+    let files = count_in_parallel(counted_paths, options.jobs(), |path| {
+        let module_tree = match &package_dirs[path] {
+            Some(dir) => &module_trees[dir],
+            None => &no_package,
+        };
+        count_package_file(path, module_tree, options)
+    })?;
+
+    Ok(DirStats::new(files, options, |path| {
+        package_dirs
+            .get(path)
+            .and_then(|dir| dir.as_ref())
+            .is_some_and(|dir| module_trees[dir].contains(path))
+    }))
+}
+
+/// Counts a single file or all Rust files under a directory.
+pub fn count_path(path: impl AsRef<Path>, options: &CountOptions) -> io::Result<DirStats> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        count_dir(path, options)
+    } else {
+        count_files([path.to_path_buf()], options)
+    }
+}
+
+impl DirStats {
+    fn new(
+        files: BTreeMap<PathBuf, FileStats>,
+        options: &CountOptions,
+        is_reachable: impl Fn(&Path) -> bool,
+    ) -> Self {
+        if options.module_tree {
+            let (reachable, unreachable) =
+                files.into_iter().partition(|(path, _)| is_reachable(path));
+            Self {
+                files: reachable,
+                unreachable: Some(unreachable),
+            }
+        } else {
+            Self {
+                files,
+                unreachable: None,
+            }
+        }
+    }
+}
+
+/// Counts lines of all Rust files of a workspace package. Files of other packages nested in the
+/// directory of the package are left out.
+pub fn count_package(
+    workspace: &Workspace,
+    package: &Package,
+    options: &CountOptions,
+) -> io::Result<DirStats> {
+    let module_tree = ModuleTree::from_targets(
+        package.targets.iter().cloned(),
+        &options.visitor.test_attributes,
+    );
+
+    let mut published = PublishedFiles::default();
+    let paths = enumerate_rust_files(&package.dir, &options.walk, options.jobs())?
+        .into_iter()
+        .filter(|path| {
+            workspace
+                .owner(path)
+                .is_some_and(|owner| owner.dir == package.dir)
+        })
+        .filter(|path| !options.packaged || published.contains(path))
+        .collect();
+    let files = count_in_parallel(paths, options.jobs(), |path| {
+        count_package_file(path, &module_tree, options)
+    })?;
+
+    Ok(DirStats::new(files, options, |path| {
+        module_tree.contains(path)
+    }))
+}
+
+/// Sums the stats of the files of each target of the packages. A file belongs to a target if it is
+/// reachable from the crate root of the target through `mod` declarations, so files shared by
+/// several targets are counted for each of them.
+pub fn count_targets(
+    packages: &[Package],
+    files: &BTreeMap<PathBuf, FileStats>,
+    options: &CountOptions,
+) -> Vec<TargetStats> {
+    let mut targets = vec![];
+    for package in packages {
+        for target in &package.targets {
+            let module_tree =
+                ModuleTree::from_targets([target.clone()], &options.visitor.test_attributes);
+            let target_files: Vec<&FileStats> = files
+                .iter()
+                .filter(|(path, _)| module_tree.contains(path))
+                .map(|(_, file_stats)| file_stats)
+                .collect();
+
+            targets.push(TargetStats {
+                package: package.name.clone(),
+                target: target.clone(),
+                file_count: target_files.len() as u64,
+                stats: target_files.iter().map(|file| &file.stats).sum(),
+            });
+        }
+    }
+
+    targets
+}
+
+/// Counts lines of the sources of dependencies without network access. Sources are looked up in
+/// `vendor_dir` first, then in the registry source cache and the git checkouts of Cargo in
+/// `cargo_home`. Dependencies with sources that are not found are reported in
+/// [`DependencyStats::missing`].
+pub fn count_dependencies(
+    dependencies: &[Dependency],
+    vendor_dir: &Path,
+    cargo_home: &Path,
+    options: &CountOptions,
+) -> io::Result<DependencyStats> {
+    let mut stats = DependencyStats::default();
+    for dependency in dependencies {
+        match dependency.find_source(vendor_dir, cargo_home) {
+            Some(dir) => {
+                let dir_stats = count_dir(&dir, options)?;
+                stats.counted.push((dependency.clone(), dir_stats));
+            }
+            None => stats.missing.push(dependency.clone()),
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Counts source code read from `reader` as if it was the file at `path`, e.g. an unsaved buffer
+/// of an editor. The file does not need to exist, its path is only used to determine the context
+/// of the code with the same rules as [`count_files`].
+pub fn count_reader_at(
+    reader: impl Read,
+    path: impl AsRef<Path>,
+    options: &CountOptions,
+) -> DirStats {
+    let path = path.as_ref();
+    let module_tree = package_dir(path)
+        .map(|dir| ModuleTree::build(&dir, &options.visitor.test_attributes))
+        .unwrap_or_default();
+
+    let is_published = !options.packaged || PublishedFiles::default().contains(path);
+    let files = is_published
+        .then(|| count_package_source(reader, path, &module_tree, options))
+        .flatten()
+        .map(|file_stats| (path.to_path_buf(), file_stats))
+        .into_iter()
+        .collect();
+
+    DirStats::new(files, options, |path| module_tree.contains(path))
+}
+
+/// Counts a file using the module tree of its package. Returns `None` for generated files that
+/// are excluded.
+fn count_package_file(
+    path: &Path,
+    module_tree: &ModuleTree,
+    options: &CountOptions,
+) -> io::Result<Option<FileStats>> {
+    Ok(count_package_source(
+        File::open(path)?,
+        path,
+        module_tree,
+        options,
+    ))
+}
+
+/// Counts source code of the file at `path`. See [`count_package_file`].
+fn count_package_source(
+    reader: impl Read,
+    path: &Path,
+    module_tree: &ModuleTree,
+    options: &CountOptions,
+) -> Option<FileStats> {
+    // Main code is classified by path conventions, e.g. `src/tests.rs` is tests
+    let category = rule_category(path, &options.category_rules).unwrap_or_else(|| {
+        module_tree
+            .context(path)
+            .filter(|context| *context != VisitorContext::Main)
+            .unwrap_or_else(|| VisitorContext::from_file_path(path))
+            .into()
+    });
+    count_source(reader, category, options)
+}
+
+/// Counts source code in the given category. Returns `None` for generated files that are
+/// excluded.
+fn count_source(
+    reader: impl Read,
+    category: Category,
+    options: &CountOptions,
+) -> Option<FileStats> {
+    // Files of user-defined categories are classified like main code, so tests and generated
+    // regions in them are still counted as such
+    let context = category.context().unwrap_or(VisitorContext::Main);
+    let mut file_stats = count_reader(reader, context, &options.visitor);
+    if category.context().is_none() {
+        file_stats.stats.move_category(&Category::Main, category);
+    }
+
+    if options.exclude_generated {
+        if file_stats
+            .generated
+            .is_some_and(|reason| reason.is_whole_file())
+        {
+            return None;
+        }
+        file_stats.stats.remove(&Category::Generated);
+    }
+
+    Some(file_stats)
+}
+
+/// Returns the category assigned to the file by the rules. Globs are matched against the path of
+/// the file relative to its package.
+fn rule_category(path: &Path, rules: &CategoryRules) -> Option<Category> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    let package_path = package_dir(path)
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+    rules.category(package_path).cloned()
+}
+
+/// Finds the directory of the closest `Cargo.toml` containing the file.
+fn package_dir(path: &Path) -> Option<PathBuf> {
+    manifest_dir(path.parent()?)
+}
+
+/// Finds the closest directory with a `Cargo.toml` among `dir` and its ancestors.
+fn manifest_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find_map(|dir| {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        dir.join("Cargo.toml").is_file().then(|| dir.to_path_buf())
+    })
+}
+
+/// Finds Rust files under `root`, searching directories with `jobs` threads. Files are sorted by
+/// path. Fails if a glob pattern is invalid.
+fn enumerate_rust_files(
+    root: impl AsRef<Path>,
+    options: &WalkOptions,
+    jobs: usize,
+) -> io::Result<Vec<PathBuf>> {
+    let root = root.as_ref();
+    let invalid_input = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+    // Excludes are applied while walking to skip whole directories. Includes are applied to the
+    // found files instead: as overrides, they would take precedence over ignore files.
+    let mut overrides = OverrideBuilder::new(root);
+    for pattern in &options.exclude {
+        overrides
+            .add(&format!("!{pattern}"))
+            .map_err(invalid_input)?;
+    }
+    let overrides = overrides.build().map_err(invalid_input)?;
+    let includes = if options.include.is_empty() {
+        None
+    } else {
+        let mut includes = GitignoreBuilder::new(root);
+        for pattern in &options.include {
+            includes.add_line(None, pattern).map_err(invalid_input)?;
+        }
+        Some(includes.build().map_err(invalid_input)?)
+    };
+
+    let mut walk = WalkBuilder::new(root);
+    walk.standard_filters(!options.no_ignore)
+        .hidden(!options.hidden)
+        .follow_links(options.follow_symlinks)
+        .overrides(overrides)
+        .threads(jobs);
+    if !options.no_ignore {
+        walk.add_custom_ignore_filename(".warlocignore");
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    walk.build_parallel().run(|| {
+        let sender = sender.clone();
+        Box::new(move |entry| {
+            let is_rust_file = |entry: &ignore::DirEntry| {
+                entry.file_type().is_some_and(|e| e.is_file())
+                    && entry
+                        .file_name()
+                        .to_str()
+                        .map(|name| name.ends_with(".rs"))
+                        .unwrap_or(false)
+            };
+            if let Some(entry) = entry.ok().filter(is_rust_file) {
+                // The receiver outlives the walk
+                let _ = sender.send(entry.into_path());
+            }
+            WalkState::Continue
+        })
+    });
+    drop(sender);
+
+    let mut files: Vec<PathBuf> = receiver
+        .into_iter()
+        .filter(|path| {
+            includes.as_ref().is_none_or(|includes| {
+                includes
+                    .matched_path_or_any_parents(path, false)
+                    .is_ignore()
+            })
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Counts the files with `jobs` threads. Threads take the next file from a shared index and
+/// results are collected by path, so they do not depend on the order files are counted in. Fails
+/// with the error of the first file that could not be counted, in the order of `paths`.
+fn count_in_parallel(
+    paths: Vec<PathBuf>,
+    jobs: usize,
+    count: impl Fn(&Path) -> io::Result<Option<FileStats>> + Sync,
+) -> io::Result<BTreeMap<PathBuf, FileStats>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, io::Result<Option<FileStats>>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, paths.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            return results;
+                        };
+                        results.push((index, count(path)));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut files = BTreeMap::new();
+    for (path, (_, result)) in paths.into_iter().zip(results) {
+        if let Some(file_stats) = result? {
+            files.insert(path, file_stats);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    #[test]
+    fn count_directory() {
+        let tree = TempTree::new("count");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write("src/lib.rs", "mod a;\n\nfn lib() {}\n");
+        tree.write("src/a.rs", "// @generated\nfn a() {}\n");
+        tree.write("tests/it.rs", "#[test]\nfn it() {}\n");
+        tree.write("src/orphan.rs", "fn orphan() {}\n");
+
+        let options = CountOptions {
+            module_tree: true,
+            ..Default::default()
+        };
+        let stats = count_dir(root, &options).unwrap();
+        let excluded = count_dir(
+            root,
+            &CountOptions {
+                exclude_generated: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats.files.len(), 3);
+        assert_eq!(
+            stats.files[&root.join("src/a.rs")].generated,
+            Some(GeneratedReason::Marker)
+        );
+        let totals = stats.totals();
+        assert_eq!(totals[Category::Main].code, 2);
+        assert_eq!(totals[Category::Main].whitespaces, 1);
+        assert_eq!(totals[Category::Generated].sum(), 2);
+        assert_eq!(totals[Category::Tests].code, 2);
+
+        let unreachable = stats.unreachable.unwrap();
+        assert_eq!(
+            unreachable.keys().collect::<Vec<_>>(),
+            vec![&root.join("src/orphan.rs")]
+        );
+
+        assert_eq!(excluded.files.len(), 3);
+        assert!(!excluded.files.contains_key(&root.join("src/a.rs")));
+        assert_eq!(excluded.totals()[Category::Generated].sum(), 0);
+    }
+
+    #[test]
+    fn count_subdirectory() {
+        let tree = TempTree::new("subdir");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write("src/lib.rs", "#[cfg(test)]\nmod tests;\n\nfn lib() {}\n");
+        tree.write("src/tests.rs", "fn it() {}\n");
+        tree.write("src/orphan.rs", "fn orphan() {}\n");
+
+        let options = CountOptions {
+            module_tree: true,
+            ..Default::default()
+        };
+        let stats = count_dir(root.join("src"), &options).unwrap();
+
+        assert_eq!(stats.files.len(), 2);
+        let totals = stats.totals();
+        assert_eq!(totals[Category::Main].code, 1);
+        assert_eq!(totals[Category::Tests].code, 3);
+        let unreachable = stats.unreachable.unwrap();
+        assert_eq!(
+            unreachable.keys().collect::<Vec<_>>(),
+            vec![&root.join("src/orphan.rs")]
+        );
+    }
+
+    #[test]
+    fn parallel_counting() {
+        let tree = TempTree::new("parallel");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "[package]\nname = \"a\"\n");
+        for i in 0..50 {
+            tree.write(&format!("src/m{}/f{i}.rs", i % 7), &"fn f() {}\n".repeat(i));
+            tree.write(&format!("tests/t{i}.rs"), "#[test]\nfn it() {}\n");
+        }
+
+        let count = |jobs| {
+            let options = CountOptions {
+                jobs,
+                ..Default::default()
+            };
+            let dir = count_dir(root, &options).unwrap();
+            let paths: Vec<_> = dir.files.keys().cloned().collect();
+            let files = count_files(paths.clone(), &options).unwrap();
+            (paths, dir.totals(), files.totals())
+        };
+        let sequential = count(1);
+        let parallel = count(8);
+
+        assert_eq!(sequential.0.len(), 100);
+        assert_eq!(sequential.1[Category::Main].code, (0..50).sum::<u64>());
+        assert_eq!(sequential.1, sequential.2);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn walk_filters() {
+        let tree = TempTree::new("walk");
+        let root = tree.path();
+
+        tree.write("src/lib.rs", "");
+        tree.write("src/gen.rs", "");
+        tree.write("third_party/dep/lib.rs", "");
+        tree.write("snapshots/old.rs", "");
+        tree.write(".hidden/a.rs", "");
+        tree.write("src/ignored.rs", "");
+        tree.write(".warlocignore", "/snapshots\n/src/ignored.rs\n");
+
+        let files = |walk: WalkOptions| -> Vec<PathBuf> {
+            enumerate_rust_files(root, &walk, 2)
+                .unwrap()
+                .into_iter()
+                .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+                .collect()
+        };
+        let default = files(WalkOptions::default());
+        let filtered = files(WalkOptions {
+            include: vec!["src/**".into(), "third_party/**".into()],
+            exclude: vec!["third_party/".into(), "gen.rs".into()],
+            ..Default::default()
+        });
+        let unfiltered = files(WalkOptions {
+            no_ignore: true,
+            hidden: true,
+            ..Default::default()
+        });
+        let invalid = enumerate_rust_files(
+            root,
+            &WalkOptions {
+                include: vec!["src/{a".into()],
+                ..Default::default()
+            },
+            1,
+        )
+        .is_err();
+
+        let paths = |paths: &[&str]| -> Vec<PathBuf> { paths.iter().map(PathBuf::from).collect() };
+        assert_eq!(
+            default,
+            paths(&["src/gen.rs", "src/lib.rs", "third_party/dep/lib.rs"])
+        );
+        assert_eq!(filtered, paths(&["src/lib.rs"]));
+        assert_eq!(
+            unfiltered,
+            paths(&[
+                ".hidden/a.rs",
+                "snapshots/old.rs",
+                "src/gen.rs",
+                "src/ignored.rs",
+                "src/lib.rs",
+                "third_party/dep/lib.rs"
+            ])
+        );
+        assert!(invalid);
+    }
+
+    #[test]
+    fn count_file_list() {
+        let tree = TempTree::new("files");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write("src/lib.rs", "#[cfg(test)]\nmod helpers;\n");
+        tree.write("src/helpers.rs", "fn helper() {}\n");
+        tree.write("src/orphan.rs", "fn orphan() {}\n");
+
+        let options = CountOptions {
+            module_tree: true,
+            ..Default::default()
+        };
+        let stats = count_files(
+            [root.join("src/helpers.rs"), root.join("src/orphan.rs")],
+            &options,
+        )
+        .unwrap();
+        let single = count_path(root.join("src/helpers.rs"), &CountOptions::default()).unwrap();
+
+        assert_eq!(
+            stats.files.keys().collect::<Vec<_>>(),
+            vec![&root.join("src/helpers.rs")]
+        );
+        assert_eq!(stats.totals()[Category::Tests].code, 1);
+        assert_eq!(stats.unreachable.unwrap().len(), 1);
+        assert_eq!(single.totals()[Category::Tests].code, 1);
+    }
+
+    #[test]
+    fn count_reader_with_path_hint() {
+        let source = "#[test]\nfn it() {}\n";
+        let options = CountOptions::default();
+
+        let stats = count_reader_at(source.as_bytes(), "tests/it.rs", &options);
+        assert_eq!(stats.totals()[Category::Tests].code, 2);
+        assert!(stats.files.contains_key(Path::new("tests/it.rs")));
+
+        let stats = count_reader_at("fn main() {}\n".as_bytes(), "examples/demo.rs", &options);
+        assert_eq!(stats.totals()[Category::Examples].code, 1);
+
+        let options = CountOptions {
+            category_rules: CategoryRules::new(["tests/data/**=test-data".parse().unwrap()])
+                .unwrap(),
+            ..Default::default()
+        };
+        let source = "fn data() {}\n\n#[test]\nfn it() {}\n";
+        let totals = count_reader_at(source.as_bytes(), "tests/data/a.rs", &options).totals();
+        let test_data = Category::Custom("test-data".into());
+        assert_eq!(totals[test_data.clone()].code, 1);
+        assert_eq!(totals[Category::Tests].code, 2);
+        assert_eq!(totals[Category::Main].code, 0);
+        assert_eq!(
+            totals.iter().map(|(category, _)| category).last(),
+            Some(&test_data)
+        );
+    }
+
+    #[test]
+    fn count_locked_dependencies() {
+        let tree = TempTree::new("dependencies");
+        let root = tree.path();
+
+        tree.write(
+            "vendor/dep/Cargo.toml",
+            "[package]\nname = \"dep\"\nversion = \"1.0.0\"\n",
+        );
+        tree.write(
+            "vendor/dep/src/lib.rs",
+            "fn dep() {}\n\n#[test]\nfn it() {}\n",
+        );
+
+        let dependency = |name: &str| Dependency {
+            name: name.into(),
+            version: "1.0.0".into(),
+            source: "registry+https://github.com/rust-lang/crates.io-index".into(),
+        };
+        let stats = count_dependencies(
+            &[dependency("dep"), dependency("missing")],
+            &root.join("vendor"),
+            &root.join("home"),
+            &CountOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(stats.counted.len(), 1);
+        assert_eq!(stats.counted[0].0.name, "dep");
+        let totals = stats.counted[0].1.totals();
+        assert_eq!(totals[Category::Main].code, 1);
+        assert_eq!(totals[Category::Tests].code, 2);
+        assert_eq!(stats.missing, vec![dependency("missing")]);
+    }
+
+    #[test]
+    fn count_workspace_package() {
+        let tree = TempTree::new("package");
+        let root = tree.path();
+
+        tree.write(
+            "Cargo.toml",
+            "[package]\nname = \"root\"\n\n[workspace]\nmembers = [\"inner\"]\n",
+        );
+        tree.write("src/lib.rs", "fn root() {}\n");
+        tree.write("inner/Cargo.toml", "[package]\nname = \"inner\"\n");
+        tree.write("inner/src/lib.rs", "fn inner() {}\n");
+
+        let workspace = Workspace::load(Some(&root.join("Cargo.toml"))).unwrap();
+        let package = workspace
+            .packages
+            .iter()
+            .find(|package| package.name == "root")
+            .unwrap();
+        let stats = count_package(&workspace, package, &CountOptions::default()).unwrap();
+
+        assert_eq!(stats.files.len(), 1);
+        assert!(stats.files.keys().all(|path| path.ends_with("src/lib.rs")));
+    }
+
+    #[test]
+    fn count_custom_target_paths() {
+        let tree = TempTree::new("targets");
+        let root = tree.path();
+
+        tree.write(
+            "Cargo.toml",
+            "[package]\nname = \"a\"\n\n[[test]]\nname = \"it\"\npath = \"it/main.rs\"\n\n\
+             [[example]]\nname = \"demo\"\npath = \"demos/demo.rs\"\n",
+        );
+        tree.write("src/lib.rs", "mod shared;\nfn lib() {}\n");
+        tree.write("src/shared.rs", "fn shared() {}\n");
+        tree.write(
+            "src/main.rs",
+            "#[path = \"shared.rs\"]\nmod shared;\nfn main() {}\n",
+        );
+        tree.write("it/main.rs", "mod helpers;\nfn it() {}\n");
+        tree.write("it/helpers.rs", "fn helper() {}\n");
+        tree.write("demos/demo.rs", "fn main() {}\n");
+
+        let options = CountOptions::default();
+        let stats = count_dir(root, &options).unwrap();
+        let packages = find_packages(root);
+        let targets = count_targets(&packages, &stats.files, &options);
+
+        let totals = stats.totals();
+        assert_eq!(totals[Category::Tests].code, 3);
+        assert_eq!(totals[Category::Examples].code, 1);
+        assert_eq!(totals[Category::Main].code, 6);
+
+        let summary: Vec<(String, String, u64)> = targets
+            .iter()
+            .map(|t| {
+                (
+                    t.target.kind.to_string(),
+                    t.target.name.clone(),
+                    t.file_count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("lib".into(), "a".into(), 2),
+                ("bin".into(), "a".into(), 2),
+                ("test".into(), "it".into(), 2),
+                ("example".into(), "demo".into(), 1),
+            ]
+        );
+        assert_eq!(targets[2].stats[Category::Tests].code, 3);
+    }
+}
@@ -1,53 +1,355 @@
 mod cli;
+mod config;
 mod output;
-mod visitor;
-mod warlocs;
+#[cfg(test)]
+mod test_utils;
 
 use std::{
     collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs, io,
     path::{Path, PathBuf},
+    process,
 };
 
+use cargo_warloc::{
+    cargo_home, check_thresholds, count_crate_archive, count_dependencies, count_files,
+    count_package, count_path, count_reader_at, count_targets, find_packages, CategoryRules,
+    CountOptions, Dependency, DirStats, DirTree, FileStats, Package, PackageSelection, Target,
+    VisitorOptions, WalkOptions, Workspace,
+};
 use clap::Parser;
 use cli::{CargoCli, Cli};
-use ignore::Walk;
-use visitor::Visitor;
-use warlocs::Warlocs;
+use config::Config;
+use output::{Subtotal, Subtotals};
 
 use crate::output::{output_multiple_file_stats, output_total_stats};
 
 fn main() {
-    let CargoCli::Command(args) = CargoCli::parse();
+    let CargoCli::Command(mut args) = CargoCli::parse();
+    let config_dir = match &args.manifest_path {
+        Some(manifest_path) => manifest_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf(),
+        None => env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+    };
+    Config::load(&config_dir, args.config.as_deref())
+        .unwrap_or_else(|e| exit_with_error(e))
+        .apply(&mut args);
+    let output_format = args.output_format.clone().unwrap_or_default();
 
-    let root_dir = PathBuf::from(".");
+    let options = CountOptions {
+        visitor: VisitorOptions {
+            debug: args.debug,
+            test_attributes: args.test_attributes.clone(),
+        },
+        category_rules: CategoryRules::new(args.category_rules.clone())
+            .unwrap_or_else(|e| exit_with_error(e)),
+        walk: WalkOptions {
+            include: args.include_globs.clone(),
+            exclude: args.exclude_globs.clone(),
+            no_ignore: args.no_ignore,
+            hidden: args.hidden,
+            follow_symlinks: args.follow_symlinks,
+        },
+        module_tree: args.module_tree,
+        exclude_generated: args.exclude_generated,
+        packaged: args.packaged,
+        jobs: args.jobs.unwrap_or_default(),
+    };
 
-    let files_stats: BTreeMap<PathBuf, Warlocs> = enumerate_rust_files(&root_dir)
-        .map(|p| (p.clone(), calculate_file_stats(&p, &args)))
-        .collect();
+    let is_package_mode =
+        args.manifest_path.is_some() || args.workspace || !args.packages.is_empty();
+    let (groups, packages, mut subtotals) = if args.dependencies {
+        let groups = count_locked_dependencies(&args, &options);
+        let subtotals = vec![Subtotals::Dependencies(subtotal_entries(&groups))];
+        (groups, vec![], subtotals)
+    } else if is_package_mode {
+        let (groups, packages) = count_packages(&args, &options);
+        let subtotals = vec![Subtotals::Packages(subtotal_entries(&groups))];
+        (groups, packages, subtotals)
+    } else {
+        let groups = count_roots(&args, &options);
+        let packages = if args.by_target {
+            root_dirs(&args).flat_map(find_packages).collect()
+        } else {
+            vec![]
+        };
+        let subtotals = if groups.len() > 1 {
+            vec![Subtotals::Roots(subtotal_entries(&groups))]
+        } else {
+            vec![]
+        };
+        (groups, packages, subtotals)
+    };
+
+    let mut files_stats = BTreeMap::new();
+    let mut unreachable_stats = options.module_tree.then(BTreeMap::new);
+    for (_, stats) in groups {
+        files_stats.extend(stats.files);
+        if let (Some(all), Some(unreachable)) = (&mut unreachable_stats, stats.unreachable) {
+            all.extend(unreachable);
+        }
+    }
+
+    if args.by_target {
+        let show_package = packages.len() > 1;
+        let targets = count_targets(&packages, &files_stats, &options)
+            .into_iter()
+            .map(|target| {
+                let name = format!("{} {}", target.target.kind, target.target.name);
+                Subtotal {
+                    name: if show_package {
+                        format!("{} {name}", target.package)
+                    } else {
+                        name
+                    },
+                    file_count: target.file_count,
+                    stats: target.stats,
+                }
+            })
+            .collect();
+        subtotals.push(Subtotals::Targets(targets));
+    }
+
+    if args.by_dir {
+        subtotals.push(Subtotals::Dirs(Box::new(DirTree::build(
+            &files_stats,
+            args.depth,
+        ))));
+    }
+
+    let total_stats = files_stats.values().map(|file| &file.stats).sum();
+    let exceeded = check_thresholds(&args.thresholds, &total_stats);
 
     if args.by_file {
-        output_multiple_file_stats(&args.output_format, files_stats);
+        output_multiple_file_stats(&output_format, files_stats, unreachable_stats, subtotals);
     } else {
-        let file_count = files_stats.len();
-        let total_stats = files_stats.into_values().sum();
-        output_total_stats(file_count as u64, &total_stats, &args.output_format);
+        let unreachable_total = unreachable_stats.map(|files| {
+            (
+                files.len() as u64,
+                files.into_values().map(|file| file.stats).sum(),
+            )
+        });
+        output_total_stats(
+            files_stats.len() as u64,
+            &total_stats,
+            unreachable_total,
+            &subtotals,
+            &output_format,
+        );
+    }
+
+    for threshold in &exceeded {
+        eprintln!("error: {threshold}");
     }
+    if !exceeded.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Counts the paths, file lists or standard input given on the command line, or the current
+/// directory if none are given.
+fn count_roots(args: &Cli, options: &CountOptions) -> Vec<(String, DirStats)> {
+    let mut roots: Vec<(String, DirStats)> = vec![];
+    if args.stdin {
+        let path = args
+            .stdin_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("<stdin>"));
+        let stats = count_reader_at(io::stdin().lock(), &path, options);
+        roots.push((path.display().to_string(), stats));
+    }
+    for path in &args.paths {
+        let stats = count_path(path, options).unwrap_or_else(|e| {
+            exit_with_error(format!("failed to count {}: {e}", path.display()))
+        });
+        roots.push((path.display().to_string(), stats));
+    }
+    for path in &args.crate_files {
+        let stats = count_crate_archive(path, options).unwrap_or_else(|e| {
+            exit_with_error(format!("failed to count {}: {e}", path.display()))
+        });
+        roots.push((path.display().to_string(), stats));
+    }
+    if let Some(list_path) = &args.files_from {
+        let stats = count_files(read_file_list(list_path), options).unwrap_or_else(|e| {
+            exit_with_error(format!(
+                "failed to count files from {}: {e}",
+                list_path.display()
+            ))
+        });
+        roots.push((list_path.display().to_string(), stats));
+    }
+    if roots.is_empty() {
+        let root_dir = PathBuf::from(".");
+        let stats = count_path(&root_dir, options).unwrap_or_else(|e| {
+            exit_with_error(format!("failed to count {}: {e}", root_dir.display()))
+        });
+        roots.push((root_dir.display().to_string(), stats));
+    }
+
+    roots
 }
 
-fn calculate_file_stats(file_path: impl AsRef<Path>, args: &Cli) -> Warlocs {
-    Visitor::new(&file_path, args.debug).visit_file()
+/// Directories given on the command line, or the current directory if no paths are given.
+fn root_dirs(args: &Cli) -> impl Iterator<Item = PathBuf> + '_ {
+    let is_default = args.paths.is_empty()
+        && args.files_from.is_none()
+        && args.crate_files.is_empty()
+        && !args.stdin;
+    let default = is_default.then(|| PathBuf::from("."));
+    args.paths
+        .iter()
+        .filter(|path| path.is_dir())
+        .cloned()
+        .chain(default)
 }
 
-fn enumerate_rust_files(root: impl AsRef<Path>) -> impl Iterator<Item = PathBuf> {
-    Walk::new(root)
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry.file_type().is_some_and(|e| e.is_file())
-                && entry
-                    .file_name()
-                    .to_str()
-                    .map(|name| name.ends_with(".rs"))
-                    .unwrap_or(false)
+/// Counts the workspace packages selected on the command line. Paths of the files and packages
+/// are made relative to the current directory.
+fn count_packages(args: &Cli, options: &CountOptions) -> (Vec<(String, DirStats)>, Vec<Package>) {
+    let workspace =
+        Workspace::load(args.manifest_path.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let selection = PackageSelection {
+        packages: args.packages.clone(),
+        workspace: args.workspace,
+        exclude: args.exclude.clone(),
+    };
+    let packages = workspace
+        .select(&selection)
+        .unwrap_or_else(|e| exit_with_error(e));
+
+    let current_dir = env::current_dir().unwrap_or_default();
+    // Paths from `cargo metadata` are absolute
+    let relative = |path: PathBuf| {
+        path.strip_prefix(&current_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or(path)
+    };
+
+    let groups = packages
+        .iter()
+        .map(|package| {
+            let stats = count_package(&workspace, package, options).unwrap_or_else(|e| {
+                exit_with_error(format!("failed to count package {}: {e}", package.name))
+            });
+            let relative_files = |files: BTreeMap<PathBuf, FileStats>| {
+                files
+                    .into_iter()
+                    .map(|(path, file_stats)| (relative(path), file_stats))
+                    .collect()
+            };
+            let stats = DirStats {
+                files: relative_files(stats.files),
+                unreachable: stats.unreachable.map(relative_files),
+            };
+
+            (package.name.clone(), stats)
+        })
+        .collect();
+
+    let packages = packages
+        .into_iter()
+        .map(|package| Package {
+            name: package.name.clone(),
+            dir: relative(package.dir.clone()),
+            targets: package
+                .targets
+                .iter()
+                .map(|target| Target {
+                    root: relative(target.root.clone()),
+                    ..target.clone()
+                })
+                .collect(),
+        })
+        .collect();
+
+    (groups, packages)
+}
+
+/// Counts the dependencies locked in `Cargo.lock` of the workspace. Dependencies without sources
+/// available locally are reported on standard error.
+fn count_locked_dependencies(args: &Cli, options: &CountOptions) -> Vec<(String, DirStats)> {
+    let workspace =
+        Workspace::load(args.manifest_path.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let dependencies = Dependency::load_locked(workspace.root.join("Cargo.lock"))
+        .unwrap_or_else(|e| exit_with_error(e));
+    let cargo_home =
+        cargo_home().unwrap_or_else(|| exit_with_error("failed to find the home of Cargo"));
+
+    let stats = count_dependencies(
+        &dependencies,
+        &workspace.root.join("vendor"),
+        &cargo_home,
+        options,
+    )
+    .unwrap_or_else(|e| exit_with_error(format!("failed to count dependencies: {e}")));
+
+    for dependency in &stats.missing {
+        eprintln!("warning: sources of {dependency} are not available locally");
+    }
+    if !stats.missing.is_empty() {
+        eprintln!(
+            "warning: {} of {} dependencies were not counted",
+            stats.missing.len(),
+            dependencies.len()
+        );
+    }
+
+    stats
+        .counted
+        .into_iter()
+        .map(|(dependency, stats)| (dependency.to_string(), stats))
+        .collect()
+}
+
+fn subtotal_entries(groups: &[(String, DirStats)]) -> Vec<Subtotal> {
+    groups
+        .iter()
+        .map(|(name, stats)| Subtotal {
+            name: name.clone(),
+            file_count: stats.files.len() as u64,
+            stats: stats.totals(),
+        })
+        .collect()
+}
+
+fn exit_with_error(e: impl Display) -> ! {
+    eprintln!("error: {e}");
+    process::exit(1)
+}
+
+/// Reads a list of paths separated by new lines or NUL characters from a file or from stdin if the
+/// path is `-`. Only existing Rust files are returned.
+fn read_file_list(list_path: &Path) -> Vec<PathBuf> {
+    let contents = if list_path == Path::new("-") {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(list_path)
+    }
+    .unwrap_or_else(|e| {
+        exit_with_error(format!(
+            "failed to read file list {}: {e}",
+            list_path.display()
+        ))
+    });
+
+    let separator = if contents.contains('\0') { '\0' } else { '\n' };
+    contents
+        .split(separator)
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .filter(|path| {
+            let exists = path.is_file();
+            if !exists {
+                eprintln!("warning: skipping {}: file not found", path.display());
+            }
+            exists
         })
-        .map(|entry| entry.into_path())
+        .collect()
 }
@@ -0,0 +1,477 @@
+//! Reading of Cargo package manifests.
+//!
+//! Only the parts needed to find the crate roots of a package are read: the target tables and
+//! the target auto-discovery settings. Target paths follow the same conventions as Cargo.
+
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ignore::Walk;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetKind {
+    Lib,
+    Bin,
+    Test,
+    Example,
+    Bench,
+    BuildScript,
+}
+
+impl Display for TargetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Lib => "lib",
+            Self::Bin => "bin",
+            Self::Test => "test",
+            Self::Example => "example",
+            Self::Bench => "bench",
+            Self::BuildScript => "build-script",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Compilation target of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub kind: TargetKind,
+    pub name: String,
+    /// Crate root file of the target.
+    pub root: PathBuf,
+}
+
+/// Cargo package with its targets.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    /// Directory containing the `Cargo.toml` of the package.
+    pub dir: PathBuf,
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// Invalid `include` or `exclude` pattern.
+    Pattern(PathBuf, ignore::Error),
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "failed to parse {}: {e}", path.display()),
+            Self::Pattern(path, e) => write!(f, "invalid pattern in {}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawManifest {
+    package: Option<RawPackage>,
+    lib: Option<RawTarget>,
+    #[serde(default)]
+    bin: Vec<RawTarget>,
+    #[serde(default)]
+    test: Vec<RawTarget>,
+    #[serde(default)]
+    example: Vec<RawTarget>,
+    #[serde(default)]
+    bench: Vec<RawTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPackage {
+    name: String,
+    build: Option<RawBuild>,
+    autolib: Option<bool>,
+    autobins: Option<bool>,
+    autotests: Option<bool>,
+    autoexamples: Option<bool>,
+    autobenches: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawBuild {
+    Enabled(bool),
+    Path(PathBuf),
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawTarget {
+    name: Option<String>,
+    path: Option<PathBuf>,
+}
+
+impl Package {
+    /// Reads the package from its `Cargo.toml`. Returns `None` for virtual workspace manifests.
+    pub fn load(manifest_path: impl AsRef<Path>) -> Result<Option<Self>, ManifestError> {
+        let manifest_path = manifest_path.as_ref();
+        let contents = fs::read_to_string(manifest_path)
+            .map_err(|e| ManifestError::Io(manifest_path.to_path_buf(), e))?;
+        let manifest: RawManifest = toml::from_str(&contents)
+            .map_err(|e| ManifestError::Parse(manifest_path.to_path_buf(), e))?;
+
+        let dir = manifest_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        Ok(Self::from_raw(dir, manifest, PackageFiles::FileSystem))
+    }
+
+    /// Reads the package from the contents of its `Cargo.toml` located in `dir`, with targets
+    /// found among the given files instead of on the file system, e.g. the files of a `.crate`
+    /// archive. Returns `None` for virtual workspace manifests.
+    pub fn from_listed_files(
+        manifest: &str,
+        dir: impl Into<PathBuf>,
+        files: &BTreeSet<PathBuf>,
+    ) -> Result<Option<Self>, toml::de::Error> {
+        let manifest: RawManifest = toml::from_str(manifest)?;
+        Ok(Self::from_raw(
+            dir.into(),
+            manifest,
+            PackageFiles::Listed(files),
+        ))
+    }
+
+    fn from_raw(dir: PathBuf, manifest: RawManifest, files: PackageFiles) -> Option<Self> {
+        let package = manifest.package?;
+        let mut targets = vec![];
+
+        let lib_path = manifest
+            .lib
+            .as_ref()
+            .and_then(|lib| lib.path.clone())
+            .unwrap_or_else(|| PathBuf::from("src/lib.rs"));
+        if (manifest.lib.is_some() || package.autolib != Some(false))
+            && files.is_file(&dir.join(&lib_path))
+        {
+            let name = manifest
+                .lib
+                .and_then(|lib| lib.name)
+                .unwrap_or_else(|| package.name.replace('-', "_"));
+            targets.push(Target {
+                kind: TargetKind::Lib,
+                name,
+                root: dir.join(lib_path),
+            });
+        }
+
+        let has_main = files.is_file(&dir.join("src/main.rs"));
+        let explicit_targets = [
+            (TargetKind::Bin, manifest.bin, "src/bin", package.autobins),
+            (TargetKind::Test, manifest.test, "tests", package.autotests),
+            (
+                TargetKind::Example,
+                manifest.example,
+                "examples",
+                package.autoexamples,
+            ),
+            (
+                TargetKind::Bench,
+                manifest.bench,
+                "benches",
+                package.autobenches,
+            ),
+        ];
+        for (kind, explicit, auto_dir, auto) in explicit_targets {
+            let mut kind_targets = vec![];
+            for target in explicit {
+                let name = target.name.unwrap_or_else(|| package.name.clone());
+                let path = target.path.or_else(|| {
+                    let candidates = [
+                        PathBuf::from(format!("{auto_dir}/{name}.rs")),
+                        PathBuf::from(format!("{auto_dir}/{name}/main.rs")),
+                    ];
+                    let main = (kind == TargetKind::Bin && name == package.name)
+                        .then(|| PathBuf::from("src/main.rs"));
+                    main.into_iter()
+                        .chain(candidates)
+                        .find(|path| files.is_file(&dir.join(path)))
+                });
+
+                if let Some(path) = path {
+                    kind_targets.push(Target {
+                        kind,
+                        name,
+                        root: dir.join(path),
+                    });
+                }
+            }
+
+            if auto != Some(false) {
+                let mut discovered = discover_targets(&dir.join(auto_dir), kind, files);
+                if kind == TargetKind::Bin && has_main {
+                    discovered.insert(
+                        0,
+                        Target {
+                            kind,
+                            name: package.name.clone(),
+                            root: dir.join("src/main.rs"),
+                        },
+                    );
+                }
+
+                for target in discovered {
+                    if !kind_targets
+                        .iter()
+                        .any(|t| t.root == target.root || t.name == target.name)
+                    {
+                        kind_targets.push(target);
+                    }
+                }
+            }
+
+            targets.extend(kind_targets);
+        }
+
+        let build = match package.build {
+            Some(RawBuild::Enabled(false)) => None,
+            Some(RawBuild::Path(path)) => Some(path),
+            Some(RawBuild::Enabled(true)) | None => Some(PathBuf::from("build.rs")),
+        };
+        if let Some(build) = build.filter(|path| files.is_file(&dir.join(path))) {
+            targets.push(Target {
+                kind: TargetKind::BuildScript,
+                name: "build-script-build".to_string(),
+                root: dir.join(build),
+            });
+        }
+
+        Some(Self {
+            name: package.name,
+            dir,
+            targets,
+        })
+    }
+}
+
+/// Finds all packages under `root` by their `Cargo.toml` files. Manifests that cannot be read
+/// are reported to stderr and skipped.
+pub fn find_packages(root: impl AsRef<Path>) -> Vec<Package> {
+    Walk::new(root)
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name() == "Cargo.toml")
+        .filter_map(|entry| {
+            Package::load(entry.path()).unwrap_or_else(|e| {
+                eprintln!("warning: {e}");
+                None
+            })
+        })
+        .collect()
+}
+
+/// Where the files of a package are looked up.
+#[derive(Debug, Clone, Copy)]
+enum PackageFiles<'a> {
+    FileSystem,
+    /// Paths of all files of the package, e.g. the entries of an archive.
+    Listed(&'a BTreeSet<PathBuf>),
+}
+
+impl PackageFiles<'_> {
+    fn is_file(&self, path: &Path) -> bool {
+        match self {
+            Self::FileSystem => path.is_file(),
+            Self::Listed(files) => files.contains(path),
+        }
+    }
+
+    /// Returns the paths of the entries of the directory, with a flag set for subdirectories.
+    fn entries(&self, dir: &Path) -> Vec<(PathBuf, bool)> {
+        match self {
+            Self::FileSystem => {
+                let Ok(entries) = dir.read_dir() else {
+                    return vec![];
+                };
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| {
+                        let path = entry.path();
+                        let is_dir = path.is_dir();
+                        (path, is_dir)
+                    })
+                    .collect()
+            }
+            Self::Listed(files) => {
+                let mut entries: Vec<(PathBuf, bool)> = files
+                    .iter()
+                    .filter_map(|path| {
+                        let mut rest = path.strip_prefix(dir).ok()?.components();
+                        let name = rest.next()?;
+                        Some((dir.join(name), rest.next().is_some()))
+                    })
+                    .collect();
+                entries.dedup();
+                entries
+            }
+        }
+    }
+}
+
+/// Finds targets in the auto-discovery directory: every `*.rs` file and every `*/main.rs` file.
+fn discover_targets(dir: &Path, kind: TargetKind, files: PackageFiles) -> Vec<Target> {
+    let mut targets: Vec<Target> = files
+        .entries(dir)
+        .into_iter()
+        .filter_map(|(path, is_dir)| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            if is_dir {
+                let root = path.join("main.rs");
+                files.is_file(&root).then_some(Target { kind, name, root })
+            } else {
+                path.extension()
+                    .is_some_and(|ext| ext == "rs")
+                    .then_some(Target {
+                        kind,
+                        name,
+                        root: path,
+                    })
+            }
+        })
+        .collect();
+    targets.sort_by(|a, b| a.root.cmp(&b.root));
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    /// Loads a package with the given files in a directory named after the test.
+    fn package(test: &str, manifest: &str, files: &[&str]) -> Vec<(TargetKind, String, PathBuf)> {
+        let tree = TempTree::new(&format!("manifest-{test}"));
+        for file in files {
+            tree.write(file, "");
+        }
+        tree.write("Cargo.toml", manifest);
+
+        let package = Package::load(tree.path().join("Cargo.toml"))
+            .unwrap()
+            .unwrap();
+
+        package
+            .targets
+            .into_iter()
+            .map(|t| {
+                (
+                    t.kind,
+                    t.name,
+                    t.root.strip_prefix(tree.path()).unwrap().into(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn auto_discovered_targets() {
+        let targets = package(
+            "auto",
+            "[package]\nname = \"my-crate\"\n",
+            &[
+                "src/lib.rs",
+                "src/main.rs",
+                "src/bin/tool.rs",
+                "src/bin/other/main.rs",
+                "tests/it.rs",
+                "examples/demo.rs",
+                "benches/bench.rs",
+                "build.rs",
+            ],
+        );
+
+        assert_eq!(
+            targets,
+            vec![
+                (TargetKind::Lib, "my_crate".into(), "src/lib.rs".into()),
+                (TargetKind::Bin, "my-crate".into(), "src/main.rs".into()),
+                (
+                    TargetKind::Bin,
+                    "other".into(),
+                    "src/bin/other/main.rs".into()
+                ),
+                (TargetKind::Bin, "tool".into(), "src/bin/tool.rs".into()),
+                (TargetKind::Test, "it".into(), "tests/it.rs".into()),
+                (
+                    TargetKind::Example,
+                    "demo".into(),
+                    "examples/demo.rs".into()
+                ),
+                (TargetKind::Bench, "bench".into(), "benches/bench.rs".into()),
+                (
+                    TargetKind::BuildScript,
+                    "build-script-build".into(),
+                    "build.rs".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_targets() {
+        let targets = package(
+            "explicit",
+            r#"
+[package]
+name = "a"
+build = "tools/build.rs"
+autotests = false
+
+[lib]
+path = "lib/mod.rs"
+
+[[test]]
+name = "integration"
+path = "it/main.rs"
+
+[[example]]
+name = "demo"
+path = "demos/demo.rs"
+"#,
+            &[
+                "lib/mod.rs",
+                "it/main.rs",
+                "tests/ignored.rs",
+                "demos/demo.rs",
+                "tools/build.rs",
+            ],
+        );
+
+        assert_eq!(
+            targets,
+            vec![
+                (TargetKind::Lib, "a".into(), "lib/mod.rs".into()),
+                (TargetKind::Test, "integration".into(), "it/main.rs".into()),
+                (TargetKind::Example, "demo".into(), "demos/demo.rs".into()),
+                (
+                    TargetKind::BuildScript,
+                    "build-script-build".into(),
+                    "tools/build.rs".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn virtual_manifest() {
+        let tree = TempTree::new("virtual");
+        tree.write("Cargo.toml", "[workspace]\nmembers = [\"a\"]\n");
+
+        let package = Package::load(tree.path().join("Cargo.toml")).unwrap();
+
+        assert!(package.is_none());
+    }
+}
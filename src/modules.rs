@@ -0,0 +1,494 @@
+//! Module tree resolution.
+//!
+//! A file that is included into a crate only under test configuration, e.g. through
+//! `#[cfg(test)] mod test_helpers;`, is test code no matter how it is named. To find such files,
+//! module declarations are followed from the crate roots the same way the compiler does it,
+//! including `#[path = "..."]` attributes. The same module tree tells which files are not part of
+//! any crate at all.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    attribute::Attribute,
+    lexer::{Lexer, Token, TokenKind},
+    manifest::{find_packages, Target, TargetKind},
+    visitor::VisitorContext,
+};
+
+/// Declaration of a module without a body, e.g. `mod foo;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDecl {
+    pub name: String,
+    /// Value of the `#[path = "..."]` attribute.
+    pub path: Option<String>,
+    /// Directories of the inline modules the declaration is nested in.
+    pub inline_parents: Vec<String>,
+    /// The declaration or one of the enclosing modules is compiled only for tests.
+    pub is_test: bool,
+}
+
+/// Module declarations found in a single file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModuleScan {
+    /// The file is marked with an inner test attribute like `#![cfg(test)]`.
+    pub is_test: bool,
+    pub decls: Vec<ModuleDecl>,
+}
+
+struct Scope {
+    /// Directory of the inline module opening the scope, `None` for other blocks.
+    module: Option<String>,
+    is_test: bool,
+}
+
+/// Finds declarations of modules without a body in the given source.
+pub fn scan(reader: impl Read, test_attributes: &[String]) -> ModuleScan {
+    let tokens: Vec<Token> = Lexer::new(reader)
+        .filter(|token| !token.kind.is_trivia())
+        .collect();
+
+    let mut result = ModuleScan::default();
+    let mut scopes = vec![Scope {
+        module: None,
+        is_test: false,
+    }];
+    let mut attributes: Vec<Attribute> = vec![];
+
+    let mut pos = 0;
+    while let Some(token) = tokens.get(pos) {
+        pos += 1;
+
+        match token.kind {
+            TokenKind::Punct('#') => {
+                let is_inner = tokens
+                    .get(pos)
+                    .is_some_and(|t| t.kind == TokenKind::Punct('!'));
+                let open = pos + usize::from(is_inner);
+                if tokens
+                    .get(open)
+                    .is_none_or(|t| t.kind != TokenKind::Punct('['))
+                {
+                    continue;
+                }
+
+                let close = matching_bracket(&tokens, open);
+                if let Some(attribute) = Attribute::parse(&tokens[open + 1..close]) {
+                    if !is_inner {
+                        attributes.push(attribute);
+                    } else if attribute.is_test(test_attributes) {
+                        let scope = scopes.last_mut().expect("file scope is never removed");
+                        scope.is_test = true;
+                        result.is_test |= scopes.len() == 1;
+                    }
+                }
+                pos = close + 1;
+            }
+            TokenKind::Ident if token.text == "mod" => {
+                let Some(name) = tokens.get(pos).and_then(ident) else {
+                    continue;
+                };
+                let is_test = scopes.last().is_some_and(|scope| scope.is_test)
+                    || attributes.iter().any(|attr| attr.is_test(test_attributes));
+                let path = attributes
+                    .iter()
+                    .find(|attr| attr.path == "path")
+                    .and_then(|attr| attr.value())
+                    .map(str::to_string);
+
+                match tokens.get(pos + 1).map(|t| t.kind) {
+                    Some(TokenKind::Punct(';')) => {
+                        result.decls.push(ModuleDecl {
+                            name,
+                            path,
+                            inline_parents: scopes
+                                .iter()
+                                .filter_map(|scope| scope.module.clone())
+                                .collect(),
+                            is_test,
+                        });
+                    }
+                    Some(TokenKind::Punct('{')) => scopes.push(Scope {
+                        module: Some(path.unwrap_or(name)),
+                        is_test,
+                    }),
+                    _ => continue,
+                }
+
+                attributes.clear();
+                pos += 2;
+            }
+            TokenKind::Punct('{') => {
+                let is_test = scopes.last().is_some_and(|scope| scope.is_test)
+                    || attributes.iter().any(|attr| attr.is_test(test_attributes));
+                scopes.push(Scope {
+                    module: None,
+                    is_test,
+                });
+                attributes.clear();
+            }
+            TokenKind::Punct('}') => {
+                if scopes.len() > 1 {
+                    scopes.pop();
+                }
+                attributes.clear();
+            }
+            TokenKind::Punct(';') => attributes.clear(),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Returns the position of the bracket closing the one at `open`, or the end of the tokens.
+fn matching_bracket(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (pos, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::Punct('[') => depth += 1,
+            TokenKind::Punct(']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return pos;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tokens.len()
+}
+
+fn ident(token: &Token) -> Option<String> {
+    match token.kind {
+        TokenKind::Ident => Some(token.text.clone()),
+        TokenKind::RawIdent => Some(token.text.trim_start_matches("r#").to_string()),
+        _ => None,
+    }
+}
+
+/// Where the source files of crates are read from.
+#[derive(Debug, Clone, Copy)]
+pub enum SourceFiles<'a> {
+    FileSystem,
+    /// Contents of the files by normalized path, e.g. the entries of an archive.
+    InMemory(&'a BTreeMap<PathBuf, Vec<u8>>),
+}
+
+impl SourceFiles<'_> {
+    fn is_file(&self, path: &Path) -> bool {
+        match self {
+            Self::FileSystem => path.is_file(),
+            Self::InMemory(files) => files.contains_key(&normalize(path)),
+        }
+    }
+
+    /// Finds the module declarations of the file. Returns `None` if it cannot be read.
+    fn scan(&self, path: &Path, test_attributes: &[String]) -> Option<ModuleScan> {
+        match self {
+            Self::FileSystem => Some(scan(File::open(path).ok()?, test_attributes)),
+            Self::InMemory(files) => Some(scan(&files.get(&normalize(path))?[..], test_attributes)),
+        }
+    }
+}
+
+/// Returns the file a module declaration in `file` refers to, if it exists.
+///
+/// `owns_directory` is true for crate roots, `mod.rs` files and files included with a `#[path]`
+/// attribute: modules declared in them are looked up next to them instead of in a directory
+/// named after the file.
+fn resolve(
+    file: &Path,
+    owns_directory: bool,
+    decl: &ModuleDecl,
+    files: SourceFiles,
+) -> Option<(PathBuf, bool)> {
+    let file_dir = file.parent().unwrap_or(Path::new(""));
+
+    if let (Some(path), true) = (&decl.path, decl.inline_parents.is_empty()) {
+        let path = file_dir.join(path);
+        return files.is_file(&path).then_some((path, true));
+    }
+
+    let mut dir = file_dir.to_path_buf();
+    if !owns_directory {
+        dir.push(file.file_stem()?);
+    }
+    dir.extend(&decl.inline_parents);
+
+    if let Some(path) = &decl.path {
+        let path = dir.join(path);
+        return files.is_file(&path).then_some((path, true));
+    }
+
+    let path = dir.join(format!("{}.rs", decl.name));
+    if files.is_file(&path) {
+        return Some((path, false));
+    }
+
+    let path = dir.join(&decl.name).join("mod.rs");
+    files.is_file(&path).then_some((path, true))
+}
+
+/// Files of the crates found under a directory, reachable from the crate roots through module
+/// declarations.
+#[derive(Debug, Default, Clone)]
+pub struct ModuleTree {
+    /// Normalized paths of the files, mapped to the context the file is compiled in.
+    modules: HashMap<PathBuf, VisitorContext>,
+}
+
+impl ModuleTree {
+    /// Follows module declarations from the roots of all targets of all packages under `root`.
+    pub fn build(root: &Path, test_attributes: &[String]) -> Self {
+        let targets = find_packages(root)
+            .into_iter()
+            .flat_map(|package| package.targets);
+
+        Self::from_targets(targets, test_attributes)
+    }
+
+    /// Follows module declarations from the roots of the given targets. The context of a target
+    /// is determined by its kind, so integration tests and examples outside of the `tests` and
+    /// `examples` directories are recognized too.
+    pub fn from_targets(
+        targets: impl IntoIterator<Item = Target>,
+        test_attributes: &[String],
+    ) -> Self {
+        Self::from_targets_in(targets, test_attributes, SourceFiles::FileSystem)
+    }
+
+    /// Follows module declarations from the roots of the given targets, reading the files from
+    /// `files`. See [`ModuleTree::from_targets`].
+    pub fn from_targets_in(
+        targets: impl IntoIterator<Item = Target>,
+        test_attributes: &[String],
+        files: SourceFiles,
+    ) -> Self {
+        let roots = targets.into_iter().map(|target| {
+            let context = target_context(&target);
+            (target.root, context)
+        });
+
+        Self::from_roots_in(roots, test_attributes, files)
+    }
+
+    /// Follows module declarations from the given crate roots. All modules of a root share its
+    /// context, except for modules compiled only for tests.
+    pub fn from_roots(
+        roots: impl IntoIterator<Item = (PathBuf, VisitorContext)>,
+        test_attributes: &[String],
+    ) -> Self {
+        Self::from_roots_in(roots, test_attributes, SourceFiles::FileSystem)
+    }
+
+    fn from_roots_in(
+        roots: impl IntoIterator<Item = (PathBuf, VisitorContext)>,
+        test_attributes: &[String],
+        files: SourceFiles,
+    ) -> Self {
+        let mut queue: VecDeque<(PathBuf, bool, VisitorContext)> = roots
+            .into_iter()
+            .map(|(path, context)| (normalize(path), true, context))
+            .collect();
+        let mut modules = HashMap::new();
+
+        while let Some((path, owns_directory, context)) = queue.pop_front() {
+            // A file reachable from the main code of a crate is main code, so it is visited
+            // again if it was first reached from another target kind
+            match modules.get(&path) {
+                Some(VisitorContext::Main) => continue,
+                Some(_) if context != VisitorContext::Main => continue,
+                _ => {}
+            }
+            modules.insert(path.clone(), context);
+
+            let Some(scan) = files.scan(&path, test_attributes) else {
+                continue;
+            };
+
+            for decl in &scan.decls {
+                if let Some((child, child_owns_directory)) =
+                    resolve(&path, owns_directory, decl, files)
+                {
+                    let child_context = if scan.is_test || decl.is_test {
+                        VisitorContext::Tests
+                    } else {
+                        context
+                    };
+                    queue.push_back((normalize(child), child_owns_directory, child_context));
+                }
+            }
+        }
+
+        Self { modules }
+    }
+
+    /// Returns true if the file is part of a crate.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.modules.contains_key(&normalize(path))
+    }
+
+    /// Returns the context the file is compiled in, if it is part of a crate.
+    pub fn context(&self, path: impl AsRef<Path>) -> Option<VisitorContext> {
+        self.modules.get(&normalize(path)).copied()
+    }
+}
+
+/// Returns the context the crate root of the target is compiled in, determined by the kind of the
+/// target.
+pub fn target_context(target: &Target) -> VisitorContext {
+    match target.kind {
+        TargetKind::Lib | TargetKind::Bin => VisitorContext::from_file_path(&target.root),
+        TargetKind::Test => VisitorContext::Tests,
+        TargetKind::Example => VisitorContext::Example,
+        TargetKind::Bench => VisitorContext::Benches,
+        TargetKind::BuildScript => VisitorContext::BuildScript,
+    }
+}
+
+/// Removes `.` components and resolves `..` components of the path without accessing the file
+/// system.
+pub fn normalize(path: impl AsRef<Path>) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    fn decl(name: &str, path: Option<&str>, inline_parents: &[&str], is_test: bool) -> ModuleDecl {
+        ModuleDecl {
+            name: name.to_string(),
+            path: path.map(str::to_string),
+            inline_parents: inline_parents.iter().map(|s| s.to_string()).collect(),
+            is_test,
+        }
+    }
+
+    #[test]
+    fn scan_module_decls() {
+        let source = r#"
+mod a;
+#[cfg(test)]
+pub(crate) mod b;
+#[path = "c_impl.rs"]
+mod c;
+mod d {
+    #![cfg(test)]
+    mod e;
+}
+#[cfg(test)]
+mod f {
+    mod g;
+}
+fn main() {
+    let _ = "mod h;";
+}
+"#;
+
+        let scan = scan(source.as_bytes(), &[]);
+
+        assert!(!scan.is_test);
+        assert_eq!(
+            scan.decls,
+            vec![
+                decl("a", None, &[], false),
+                decl("b", None, &[], true),
+                decl("c", Some("c_impl.rs"), &[], false),
+                decl("e", None, &["d"], true),
+                decl("g", None, &["f"], true),
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_test_file() {
+        let scan = scan("//! Helpers\n#![cfg(test)]\nmod a;\n".as_bytes(), &[]);
+
+        assert!(scan.is_test);
+        assert_eq!(scan.decls, vec![decl("a", None, &[], true)]);
+    }
+
+    #[test]
+    fn module_tree_of_package() {
+        let tree = TempTree::new("modules");
+        let root = tree.path();
+
+        tree.write("Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write(
+            "src/lib.rs",
+            "mod a;\n#[cfg(test)]\nmod helpers;\n#[cfg(test)]\n#[path = \"other/fixtures.rs\"]\nmod fixtures;\n",
+        );
+        tree.write("src/a.rs", "mod b;\n");
+        tree.write("src/a/b.rs", "");
+        tree.write("src/helpers/mod.rs", "mod nested;\n");
+        tree.write("src/helpers/nested.rs", "");
+        tree.write("src/other/fixtures.rs", "");
+
+        tree.write("src/orphan.rs", "");
+        tree.write("build.rs", "mod codegen;\n");
+        tree.write("codegen.rs", "");
+
+        let module_tree = ModuleTree::build(root, &[]);
+
+        for path in ["src/lib.rs", "src/a.rs", "src/a/b.rs"] {
+            assert!(module_tree.contains(root.join(path)), "{path}");
+            assert_eq!(
+                module_tree.context(root.join(path)),
+                Some(VisitorContext::Main),
+                "{path}"
+            );
+        }
+        for path in [
+            "src/helpers/mod.rs",
+            "src/helpers/nested.rs",
+            "src/other/fixtures.rs",
+        ] {
+            assert_eq!(
+                module_tree.context(root.join(path)),
+                Some(VisitorContext::Tests),
+                "{path}"
+            );
+        }
+        for path in ["build.rs", "codegen.rs"] {
+            assert_eq!(
+                module_tree.context(root.join(path)),
+                Some(VisitorContext::BuildScript),
+                "{path}"
+            );
+        }
+        assert!(!module_tree.contains(root.join("src/orphan.rs")));
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(
+            normalize("./src/../src/lib.rs"),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(normalize("../a/./b"), PathBuf::from("../a/b"));
+    }
+}
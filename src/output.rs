@@ -1,40 +1,156 @@
 //! Output routines.
 
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use csv::Writer as CsvWriter;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 
-use crate::{cli::OutputFormat, warlocs::Warlocs};
+use cargo_warloc::{DirTree, FileStats, Warlocs};
 
-/// Prints to stdout multiple stats formatted based on the given `output_format`.
-pub fn output_multiple_file_stats(output_format: &OutputFormat, stats: BTreeMap<PathBuf, Warlocs>) {
+use crate::cli::OutputFormat;
+
+/// Totals of groups of files.
+#[derive(Debug, Clone)]
+pub enum Subtotals {
+    /// Files found under each of the paths given on the command line.
+    Roots(Vec<Subtotal>),
+    /// Files of each package of a workspace.
+    Packages(Vec<Subtotal>),
+    /// Files of each Cargo target. A file can belong to several targets.
+    Targets(Vec<Subtotal>),
+    /// Files of each locked dependency.
+    Dependencies(Vec<Subtotal>),
+    /// Files under each directory.
+    Dirs(Box<DirTree>),
+}
+
+impl Subtotals {
+    /// Returns the groups as a flat list. Directories are listed depth-first, named by their
+    /// paths.
+    fn entries(&self) -> Vec<Subtotal> {
+        match self {
+            Self::Roots(entries)
+            | Self::Packages(entries)
+            | Self::Targets(entries)
+            | Self::Dependencies(entries) => entries.clone(),
+            Self::Dirs(tree) => tree
+                .flatten()
+                .into_iter()
+                .map(|(_, dir)| Subtotal {
+                    name: dir.path.display().to_string(),
+                    file_count: dir.file_count,
+                    stats: dir.stats.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Title of a single group of files.
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Roots(_) => "Root",
+            Self::Packages(_) => "Package",
+            Self::Targets(_) => "Target",
+            Self::Dependencies(_) => "Dependency",
+            Self::Dirs(_) => "Directory",
+        }
+    }
+
+    /// Key of the groups in serialized output.
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Roots(_) => "roots",
+            Self::Packages(_) => "packages",
+            Self::Targets(_) => "targets",
+            Self::Dependencies(_) => "dependencies",
+            Self::Dirs(_) => "dirs",
+        }
+    }
+}
+
+/// Serializes the entries of the groups, or the nested directories of a directory tree.
+impl Serialize for Subtotals {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Roots(entries)
+            | Self::Packages(entries)
+            | Self::Targets(entries)
+            | Self::Dependencies(entries) => entries.serialize(serializer),
+            Self::Dirs(tree) => tree.serialize(serializer),
+        }
+    }
+}
+
+/// Serializes subtotals as a map from the key of each kind of group to the entries.
+fn serialize_subtotals<S: Serializer>(
+    subtotals: &[Subtotals],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        subtotals
+            .iter()
+            .map(|subtotals| (subtotals.key(), subtotals)),
+    )
+}
+
+/// Totals of the files of one group.
+#[derive(Debug, Clone, Serialize)]
+pub struct Subtotal {
+    pub name: String,
+    pub file_count: u64,
+    #[serde(flatten)]
+    pub stats: Warlocs,
+}
+
+/// Prints to stdout multiple stats formatted based on the given `output_format`. Stats of files
+/// that are not part of any crate and subtotals of groups of files are printed separately if
+/// given.
+pub fn output_multiple_file_stats(
+    output_format: &OutputFormat,
+    stats: BTreeMap<PathBuf, FileStats>,
+    unreachable: Option<BTreeMap<PathBuf, FileStats>>,
+    subtotals: Vec<Subtotals>,
+) {
     match output_format {
         OutputFormat::Tabular => {
             println!("Total file count: {}", stats.len());
-            for (path, warloc) in stats.iter() {
-                println!("\nFile path : {}", path.to_str().unwrap());
-                single_stat_tabular(warloc);
+            for (path, file_stats) in stats.iter() {
+                single_file_tabular(path, file_stats);
+            }
+
+            if let Some(unreachable) = unreachable {
+                println!("\nUnreachable file count: {}", unreachable.len());
+                for (path, file_stats) in unreachable.iter() {
+                    single_file_tabular(path, file_stats);
+                }
             }
+
+            subtotals_tabular(&subtotals);
         }
         OutputFormat::Json => {
-            let multistats = SerializableMultiFileStats::from_file_stats(stats);
+            let multistats =
+                SerializableMultiFileStats::from_file_stats(stats, unreachable, subtotals);
             println!(
                 "{}",
                 serde_json::to_string(&multistats).expect("JSON serialization should work")
             )
         }
         OutputFormat::Yaml => {
-            let multistats = SerializableMultiFileStats::from_file_stats(stats);
+            let multistats =
+                SerializableMultiFileStats::from_file_stats(stats, unreachable, subtotals);
             print!(
                 "{}",
                 serde_yaml_bw::to_string(&multistats).expect("YAML serialization should work")
             )
         }
         OutputFormat::Csv => {
-            let multistats = SerializableMultiFileStats::from_file_stats(stats);
+            let multistats =
+                SerializableMultiFileStats::from_file_stats(stats, unreachable, subtotals);
             let mut buff: Vec<u8> = Vec::new();
-            csv_output::output_csv(&multistats.totals, &multistats.files, &mut buff);
+            csv_output::output_csv(&multistats, &mut buff);
             print!(
                 "{}",
                 str::from_utf8(&buff).expect("CSV output should be UTF-8")
@@ -44,21 +160,38 @@ pub fn output_multiple_file_stats(output_format: &OutputFormat, stats: BTreeMap<
 }
 
 /// Prints to stdout the provided total [Warloc] stats formatted to the given `output_format`.
-pub fn output_total_stats(file_count: u64, stats: &Warlocs, output_format: &OutputFormat) {
+/// Total stats of files that are not part of any crate and subtotals of groups of files are
+/// printed separately if given.
+pub fn output_total_stats(
+    file_count: u64,
+    stats: &Warlocs,
+    unreachable: Option<(u64, Warlocs)>,
+    subtotals: &[Subtotals],
+    output_format: &OutputFormat,
+) {
     match output_format {
         OutputFormat::Tabular => {
             println!("Total file count: {file_count}",);
-            single_stat_tabular(stats)
+            single_stat_tabular(stats);
+
+            if let Some((file_count, stats)) = unreachable {
+                println!("\nUnreachable file count: {file_count}");
+                single_stat_tabular(&stats);
+            }
+
+            subtotals_tabular(subtotals);
         }
         OutputFormat::Json => {
-            let stats = SerializableTotalStats { file_count, stats };
+            let stats =
+                SerializableTotalStats::new(file_count, stats, unreachable.as_ref(), subtotals);
             println!(
                 "{}",
                 serde_json::to_string(&stats).expect("JSON serialization should work")
             )
         }
         OutputFormat::Yaml => {
-            let stats = SerializableTotalStats { file_count, stats };
+            let stats =
+                SerializableTotalStats::new(file_count, stats, unreachable.as_ref(), subtotals);
             print!(
                 "{}",
                 serde_yaml_bw::to_string(&stats).expect("YAML serialization should work")
@@ -67,14 +200,54 @@ pub fn output_total_stats(file_count: u64, stats: &Warlocs, output_format: &Outp
         OutputFormat::Csv => {
             let mut buff: Vec<u8> = Vec::new();
             let mut writer = CsvWriter::from_writer(&mut buff);
+            let subtotals: Vec<_> = subtotals.iter().flat_map(Subtotals::entries).collect();
+            let categories = csv_output::categories(
+                [stats]
+                    .into_iter()
+                    .chain(unreachable.iter().map(|(_, stats)| stats))
+                    .chain(subtotals.iter().map(|subtotal| &subtotal.stats)),
+            );
+            let has_files_column = unreachable.is_some() || !subtotals.is_empty();
+            if has_files_column {
+                writer.write_field("Files").expect("Write CSV first row");
+            }
             writer
                 .write_field("File Count")
                 .expect("Write CSV first row");
-            csv_output::write_csv_header_row(&mut writer);
+            csv_output::write_csv_header_row(&categories, &mut writer);
+
+            if has_files_column {
+                let label = if unreachable.is_some() {
+                    "reachable"
+                } else {
+                    "total"
+                };
+                writer.write_field(label).expect("Write CSV row field");
+            }
             writer
                 .write_field(file_count.to_string())
                 .expect("Write CSV row field");
-            csv_output::write_single_csv_value(stats, &mut writer);
+            csv_output::write_single_csv_value(stats, &categories, &mut writer);
+
+            if let Some((file_count, stats)) = unreachable {
+                writer
+                    .write_field("unreachable")
+                    .expect("Write CSV row field");
+                writer
+                    .write_field(file_count.to_string())
+                    .expect("Write CSV row field");
+                csv_output::write_single_csv_value(&stats, &categories, &mut writer);
+            }
+
+            for subtotal in subtotals {
+                writer
+                    .write_field(&subtotal.name)
+                    .expect("Write CSV row field");
+                writer
+                    .write_field(subtotal.file_count.to_string())
+                    .expect("Write CSV row field");
+                csv_output::write_single_csv_value(&subtotal.stats, &categories, &mut writer);
+            }
             drop(writer);
             print!(
                 "{}",
@@ -84,54 +257,119 @@ pub fn output_total_stats(file_count: u64, stats: &Warlocs, output_format: &Outp
     }
 }
 
-/// Prints to stdout a tabular representation for a single [Warlocs] values.
-fn single_stat_tabular(stats: &Warlocs) {
+/// Prints to stdout a table for each group of files, and a single table for a directory tree.
+fn subtotals_tabular(subtotals: &[Subtotals]) {
+    for subtotals in subtotals {
+        if let Subtotals::Dirs(tree) = subtotals {
+            dir_tree_tabular(tree);
+            continue;
+        }
+
+        for subtotal in subtotals.entries() {
+            println!("\n{}: {}", subtotals.label(), subtotal.name);
+            println!("File count: {}", subtotal.file_count);
+            single_stat_tabular(&subtotal.stats);
+        }
+    }
+}
+
+/// Prints to stdout the totals of each directory of the tree, with subdirectories indented under
+/// their parent.
+fn dir_tree_tabular(tree: &DirTree) {
+    let dirs: Vec<(String, &DirTree)> = tree
+        .flatten()
+        .into_iter()
+        .map(|(depth, dir)| {
+            let name = if depth == 0 {
+                dir.path.display().to_string()
+            } else {
+                let name = dir.path.file_name().unwrap_or_default().to_string_lossy();
+                format!("{}{name}", "  ".repeat(depth))
+            };
+            (name, dir)
+        })
+        .collect();
+    let width = dirs
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .chain(["Directory".len()])
+        .max()
+        .unwrap_or_default();
+
     println!(
-        "{0: <12} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12}",
-        "Type", "Code", "Blank", "Doc comments", "Comments", "Total",
+        "\n{0: <width$} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12} | {6: <12} | {7: <12}",
+        "Directory", "Files", "Code", "Blank", "Doc comments", "Doc tests", "Comments", "Total",
     );
     println!(
-        "{0:-<12}-|-{1:-<12}-|-{2:-<12}-|-{3:-<12}-|-{4:-<12}-|-{5:-<12}",
-        "", "", "", "", "", "",
+        "{0:-<width$}-|-{1:-<12}-|-{2:-<12}-|-{3:-<12}-|-{4:-<12}-|-{5:-<12}-|-{6:-<12}-|-{7:-<12}",
+        "", "", "", "", "", "", "", "",
     );
+    for (name, dir) in dirs {
+        let stats = &dir.stats;
+        println!(
+            "{0: <width$} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12} | {6: <12} | {7: <12}",
+            name,
+            dir.file_count,
+            stats.code(),
+            stats.whitespaces(),
+            stats.docs(),
+            stats.doctests(),
+            stats.comments(),
+            stats.sum(),
+        );
+    }
+}
+
+/// Prints to stdout a tabular representation of the stats of a single file.
+fn single_file_tabular(path: &Path, file_stats: &FileStats) {
+    println!("\nFile path : {}", path.to_str().unwrap());
+    if let Some(reason) = file_stats.generated {
+        println!("Generated : {reason}");
+    }
+    single_stat_tabular(&file_stats.stats);
+}
+
+/// Prints to stdout a tabular representation for a single [Warlocs] values.
+fn single_stat_tabular(stats: &Warlocs) {
+    let width = stats
+        .iter()
+        .map(|(category, _)| category.title().chars().count())
+        .chain([12])
+        .max()
+        .unwrap_or_default();
 
     println!(
-        "{0: <12} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12}",
-        "Main",
-        stats.main.code,
-        stats.main.whitespaces,
-        stats.main.docs,
-        stats.main.comments,
-        stats.main.sum(),
-    );
-    println!(
-        "{0: <12} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12}",
-        "Tests",
-        stats.tests.code,
-        stats.tests.whitespaces,
-        stats.tests.docs,
-        stats.tests.comments,
-        stats.tests.sum(),
+        "{0: <width$} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12} | {6: <12}",
+        "Type", "Code", "Blank", "Doc comments", "Doc tests", "Comments", "Total",
     );
     println!(
-        "{0: <12} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12}",
-        "Examples",
-        stats.examples.code,
-        stats.examples.whitespaces,
-        stats.examples.docs,
-        stats.examples.comments,
-        stats.examples.sum(),
+        "{0:-<width$}-|-{1:-<12}-|-{2:-<12}-|-{3:-<12}-|-{4:-<12}-|-{5:-<12}-|-{6:-<12}",
+        "", "", "", "", "", "", "",
     );
+
+    for (category, locs) in stats.iter() {
+        println!(
+            "{0: <width$} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12} | {6: <12}",
+            category.title(),
+            locs.code,
+            locs.whitespaces,
+            locs.docs,
+            locs.doctests,
+            locs.comments,
+            locs.sum(),
+        );
+    }
     println!(
-        "{0:-<12}-|-{1:-<12}-|-{2:-<12}-|-{3:-<12}-|-{4:-<12}-|-{5:-<12}",
-        "", "", "", "", "", "",
+        "{0:-<width$}-|-{1:-<12}-|-{2:-<12}-|-{3:-<12}-|-{4:-<12}-|-{5:-<12}-|-{6:-<12}",
+        "", "", "", "", "", "", "",
     );
     println!(
-        "{0: <12} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12}",
+        "{0: <width$} | {1: <12} | {2: <12} | {3: <12} | {4: <12} | {5: <12} | {6: <12}",
         "",
         stats.code(),
         stats.whitespaces(),
         stats.docs(),
+        stats.doctests(),
         stats.comments(),
         stats.sum(),
     );
@@ -142,6 +380,33 @@ struct SerializableTotalStats<'a> {
     file_count: u64,
     #[serde(flatten)]
     stats: &'a Warlocs,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unreachable: Option<Box<SerializableTotalStats<'a>>>,
+    #[serde(flatten, serialize_with = "serialize_subtotals")]
+    subtotals: &'a [Subtotals],
+}
+
+impl<'a> SerializableTotalStats<'a> {
+    fn new(
+        file_count: u64,
+        stats: &'a Warlocs,
+        unreachable: Option<&'a (u64, Warlocs)>,
+        subtotals: &'a [Subtotals],
+    ) -> Self {
+        Self {
+            file_count,
+            stats,
+            unreachable: unreachable.map(|(file_count, stats)| {
+                Box::new(SerializableTotalStats {
+                    file_count: *file_count,
+                    stats,
+                    unreachable: None,
+                    subtotals: &[],
+                })
+            }),
+            subtotals,
+        }
+    }
 }
 
 /// Simple wrapper struct representing the structure of serialized multi-file output.
@@ -149,85 +414,159 @@ struct SerializableTotalStats<'a> {
 struct SerializableMultiFileStats {
     file_count: u64,
     totals: Warlocs,
-    files: BTreeMap<PathBuf, Warlocs>,
+    files: BTreeMap<PathBuf, FileStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unreachable: Option<Box<SerializableMultiFileStats>>,
+    #[serde(flatten, serialize_with = "serialize_subtotals")]
+    subtotals: Vec<Subtotals>,
 }
 
 impl SerializableMultiFileStats {
-    fn from_file_stats(files: BTreeMap<PathBuf, Warlocs>) -> Self {
+    fn from_file_stats(
+        files: BTreeMap<PathBuf, FileStats>,
+        unreachable: Option<BTreeMap<PathBuf, FileStats>>,
+        subtotals: Vec<Subtotals>,
+    ) -> Self {
         SerializableMultiFileStats {
             file_count: files.len() as u64,
-            totals: files.values().fold(Warlocs::default(), |acc, v| acc + *v),
+            totals: files.values().map(|file| &file.stats).sum(),
             files,
+            unreachable: unreachable
+                .map(|files| Box::new(Self::from_file_stats(files, None, vec![]))),
+            subtotals,
         }
     }
 }
 
 /// Module defining CSV output routines.
 mod csv_output {
-    use std::{collections::BTreeMap, io::Write, path::PathBuf};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::PathBuf,
+    };
 
     use csv::Writer as CsvWriter;
 
-    use crate::warlocs::Warlocs;
+    use super::SerializableMultiFileStats;
+    use cargo_warloc::{Category, FileStats, Warlocs};
 
     /// Performs CSV serialization and outputs to the provided [Write].
-    pub fn output_csv(totals: &Warlocs, files: &BTreeMap<PathBuf, Warlocs>, writer: impl Write) {
+    pub fn output_csv(stats: &SerializableMultiFileStats, writer: impl Write) {
         let mut csvw = csv::Writer::from_writer(writer);
+        let subtotals: Vec<_> = stats
+            .subtotals
+            .iter()
+            .map(|subtotals| (subtotals, subtotals.entries()))
+            .collect();
+        let categories = categories(
+            [&stats.totals]
+                .into_iter()
+                .chain(
+                    stats
+                        .unreachable
+                        .iter()
+                        .map(|unreachable| &unreachable.totals),
+                )
+                .chain(
+                    subtotals
+                        .iter()
+                        .flat_map(|(_, entries)| entries.iter().map(|subtotal| &subtotal.stats)),
+                ),
+        );
 
         csvw.write_field("File").expect("Write CSV columns row");
-        write_csv_header_row(&mut csvw);
+        csvw.write_field("Generated")
+            .expect("Write CSV columns row");
+        write_csv_header_row(&categories, &mut csvw);
+
+        write_file_rows(&stats.files, &stats.totals, &categories, "files", &mut csvw);
+        if let Some(unreachable) = &stats.unreachable {
+            write_file_rows(
+                &unreachable.files,
+                &unreachable.totals,
+                &categories,
+                "unreachable files",
+                &mut csvw,
+            );
+        }
+
+        for (subtotals, entries) in subtotals {
+            for subtotal in entries {
+                csvw.write_field(format!(
+                    "{} files in {} {}",
+                    subtotal.file_count,
+                    subtotals.label().to_lowercase(),
+                    subtotal.name
+                ))
+                .expect("Write CSV totals row");
+                csvw.write_field("").expect("Write CSV totals row");
+                write_single_csv_value(&subtotal.stats, &categories, &mut csvw);
+            }
+        }
+    }
 
-        for (path, stats) in files.iter() {
+    /// Writes a row for each file followed by the totals row.
+    fn write_file_rows<T: Write>(
+        files: &BTreeMap<PathBuf, FileStats>,
+        totals: &Warlocs,
+        categories: &[Category],
+        label: &str,
+        csvw: &mut CsvWriter<T>,
+    ) {
+        for (path, file_stats) in files.iter() {
             csvw.write_field(path.to_str().expect("Filepaths should be UTF-8"))
                 .expect("Write CSV stats value");
-            write_single_csv_value(stats, &mut csvw);
+            let reason = file_stats
+                .generated
+                .map(|reason| reason.to_string())
+                .unwrap_or_default();
+            csvw.write_field(reason).expect("Write CSV stats value");
+            write_single_csv_value(&file_stats.stats, categories, csvw);
         }
 
         let file_count = files.len();
-        csvw.write_field(format!("{file_count} files total"))
+        csvw.write_field(format!("{file_count} {label} total"))
             .expect("Write CSV totals row");
-        write_single_csv_value(totals, &mut csvw);
+        csvw.write_field("").expect("Write CSV totals row");
+        write_single_csv_value(totals, categories, csvw);
+    }
+
+    /// Returns the categories to write columns for: the built-in categories and the
+    /// user-defined categories with lines counted in any of the rows.
+    pub fn categories<'a>(rows: impl IntoIterator<Item = &'a Warlocs>) -> Vec<Category> {
+        let categories: BTreeSet<&Category> = rows
+            .into_iter()
+            .flat_map(|stats| stats.iter().map(|(category, _)| category))
+            .collect();
+        categories.into_iter().cloned().collect()
     }
 
     /// Writes out the column titles row for the CSV output of [Warlocs].
-    pub fn write_csv_header_row<T: Write>(writer: &mut CsvWriter<T>) {
-        let columns = [
-            "Main Code",
-            "Main Docs",
-            "Main Comments",
-            "Main Spaces",
-            "Test Code",
-            "Test Docs",
-            "Test Comments",
-            "Test Spaces",
-            "Example Code",
-            "Example Docs",
-            "Example Comments",
-            "Example Spaces",
-        ];
+    pub fn write_csv_header_row<T: Write>(categories: &[Category], writer: &mut CsvWriter<T>) {
+        let columns = categories.iter().flat_map(|category| {
+            ["Code", "Docs", "Doc Tests", "Comments", "Spaces"]
+                .map(|column| format!("{} {column}", category.title()))
+        });
         writer.write_record(columns).expect("Write CSV columns");
     }
 
-    pub fn write_single_csv_value<T: Write>(stats: &Warlocs, writer: &mut CsvWriter<T>) {
-        let s = stats;
-
-        let row: Vec<String> = [
-            s.main.code,
-            s.main.docs,
-            s.main.comments,
-            s.main.whitespaces,
-            s.tests.code,
-            s.tests.docs,
-            s.tests.comments,
-            s.tests.whitespaces,
-            s.examples.code,
-            s.examples.docs,
-            s.examples.comments,
-            s.examples.whitespaces,
-        ]
-        .iter()
-        .map(|v| v.to_string())
-        .collect();
+    pub fn write_single_csv_value<T: Write>(
+        stats: &Warlocs,
+        categories: &[Category],
+        writer: &mut CsvWriter<T>,
+    ) {
+        let row = categories.iter().flat_map(|category| {
+            let locs = stats.get(category);
+            [
+                locs.code,
+                locs.docs,
+                locs.doctests,
+                locs.comments,
+                locs.whitespaces,
+            ]
+            .map(|v| v.to_string())
+        });
 
         writer.write_record(row).expect("Write CSV row");
     }
@@ -235,12 +574,16 @@ mod csv_output {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use cargo_warloc::{Category, DirTree, FileStats, GeneratedReason, Warlocs};
 
     use crate::{
         cli::OutputFormat,
-        output::{output_multiple_file_stats, output_total_stats},
-        warlocs::Warlocs,
+        output::{
+            csv_output, output_multiple_file_stats, output_total_stats, SerializableMultiFileStats,
+            Subtotal, Subtotals,
+        },
     };
 
     macro_rules! all_output_formats {
@@ -254,25 +597,75 @@ mod tests {
         };
     }
 
+    fn files() -> BTreeMap<PathBuf, FileStats> {
+        let mut stats = Warlocs::default();
+        stats.get_mut(Category::Custom("test-data".into())).code = 3;
+        [
+            (PathBuf::from("abc"), FileStats::default()),
+            (
+                PathBuf::from("src/123"),
+                FileStats {
+                    stats,
+                    generated: Some(GeneratedReason::Prost),
+                },
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn subtotals() -> Vec<Subtotals> {
+        let subtotal = Subtotal {
+            name: "src".to_string(),
+            file_count: 1,
+            stats: Warlocs::default(),
+        };
+        vec![
+            Subtotals::Roots(vec![subtotal.clone()]),
+            Subtotals::Targets(vec![subtotal]),
+            Subtotals::Dirs(Box::new(DirTree::build(&files(), None))),
+        ]
+    }
+
     #[test]
     fn test_output_single_no_panics() {
         let val = Warlocs::default();
 
         for output_format in all_output_formats!() {
-            output_total_stats(1, &val, &output_format);
+            output_total_stats(1, &val, None, &[], &output_format);
+            output_total_stats(1, &val, Some((2, val.clone())), &[], &output_format);
+            output_total_stats(1, &val, None, &subtotals(), &output_format);
         }
     }
 
     #[test]
     fn test_output_multiple_no_panics() {
         for output_format in all_output_formats!() {
-            let files = [
-                (PathBuf::from("abc"), Warlocs::default()),
-                (PathBuf::from("123"), Warlocs::default()),
-            ]
-            .into_iter()
-            .collect();
-            output_multiple_file_stats(&output_format, files);
+            let files = files();
+            output_multiple_file_stats(&output_format, files.clone(), None, vec![]);
+            output_multiple_file_stats(&output_format, files.clone(), Some(files.clone()), vec![]);
+            output_multiple_file_stats(&output_format, files, None, subtotals());
         }
     }
+
+    #[test]
+    fn csv_columns_of_custom_categories() {
+        let stats = SerializableMultiFileStats::from_file_stats(files(), None, vec![]);
+        let mut buff = vec![];
+        csv_output::output_csv(&stats, &mut buff);
+        let csv = String::from_utf8(buff).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+
+        assert!(rows[0].starts_with("File,Generated,Main Code,"));
+        assert!(rows[0].contains(",Tests Code,"));
+        assert!(rows[0].contains(",Build scripts Code,"));
+        assert!(rows[0].ends_with(
+            "Generated Spaces,test-data Code,test-data Docs,test-data Doc Tests,\
+             test-data Comments,test-data Spaces"
+        ));
+        assert!(rows[1].ends_with(",0,0,0,0,0"));
+        assert!(rows[2].ends_with(",3,0,0,0,0"));
+        assert!(rows[3].starts_with("2 files total,,"));
+        assert!(rows[3].ends_with(",3,0,0,0,0"));
+    }
 }
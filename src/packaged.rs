@@ -0,0 +1,221 @@
+//! Files published with a package.
+//!
+//! `cargo package` ships the files selected by the `package.include` patterns of the manifest,
+//! or all files except the ones matching `package.exclude` if there are no include patterns. Both
+//! use the gitignore syntax and can be inherited from `workspace.package`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Deserialize;
+
+use crate::{manifest::ManifestError, package_dir};
+
+/// Include and exclude rules of a package.
+#[derive(Debug, Clone)]
+pub struct PublishRules {
+    include: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+}
+
+/// Files published with the packages they belong to. Rules of each package are read once, when
+/// the first file of the package is checked.
+#[derive(Debug, Default)]
+pub struct PublishedFiles {
+    /// Rules by package directory. `None` for virtual manifests, which publish nothing.
+    packages: HashMap<PathBuf, Option<PublishRules>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawManifest {
+    package: Option<RawPackage>,
+    workspace: Option<RawWorkspace>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPackage {
+    include: Option<RawPatterns>,
+    exclude: Option<RawPatterns>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawPatterns {
+    Patterns(Vec<String>),
+    /// `include.workspace = true`
+    Inherited {
+        workspace: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct RawWorkspace {
+    package: Option<RawWorkspacePackage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawWorkspacePackage {
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+}
+
+impl PublishRules {
+    /// Reads the rules of the package in `dir` from its `Cargo.toml`. Returns `None` for virtual
+    /// workspace manifests.
+    pub fn load(dir: &Path) -> Result<Option<Self>, ManifestError> {
+        let manifest_path = dir.join("Cargo.toml");
+        let Some(package) = read_manifest(&manifest_path)?.package else {
+            return Ok(None);
+        };
+
+        let is_inherited = |patterns: &Option<RawPatterns>| {
+            matches!(patterns, Some(RawPatterns::Inherited { workspace: true }))
+        };
+        let workspace_package = if is_inherited(&package.include) || is_inherited(&package.exclude)
+        {
+            find_workspace_package(dir)?
+        } else {
+            RawWorkspacePackage::default()
+        };
+        let resolve = |patterns, inherited| match patterns {
+            Some(RawPatterns::Patterns(patterns)) => Some(patterns),
+            Some(RawPatterns::Inherited { workspace: true }) => inherited,
+            Some(RawPatterns::Inherited { workspace: false }) | None => None,
+        };
+        let include = resolve(package.include, workspace_package.include);
+        let exclude = resolve(package.exclude, workspace_package.exclude);
+
+        let matcher = |patterns: Option<Vec<String>>| {
+            patterns
+                .map(|patterns| build_matcher(dir, &patterns))
+                .transpose()
+                .map_err(|e| ManifestError::Pattern(manifest_path.clone(), e))
+        };
+        Ok(Some(Self {
+            include: matcher(include)?,
+            exclude: matcher(exclude)?,
+        }))
+    }
+
+    /// Returns true if the file of the package is published. Include patterns take precedence
+    /// over exclude patterns, like in Cargo.
+    pub fn is_published(&self, path: &Path) -> bool {
+        let matches = |matcher: &Gitignore| {
+            let path = path.strip_prefix(matcher.path()).unwrap_or(path);
+            !path.has_root() && matcher.matched_path_or_any_parents(path, false).is_ignore()
+        };
+
+        match (&self.include, &self.exclude) {
+            (Some(include), _) => matches(include),
+            (None, Some(exclude)) => !matches(exclude),
+            (None, None) => true,
+        }
+    }
+}
+
+impl PublishedFiles {
+    /// Returns true if the file is published with the package it belongs to: the package of the
+    /// closest `Cargo.toml`. Files outside of packages are not published. Packages with
+    /// manifests that cannot be read are reported to stderr and publish all files.
+    pub fn contains(&mut self, path: &Path) -> bool {
+        let Some(dir) = package_dir(path) else {
+            return false;
+        };
+
+        self.packages
+            .entry(dir)
+            .or_insert_with_key(|dir| {
+                PublishRules::load(dir).unwrap_or_else(|e| {
+                    eprintln!("warning: {e}");
+                    Some(PublishRules {
+                        include: None,
+                        exclude: None,
+                    })
+                })
+            })
+            .as_ref()
+            .is_some_and(|rules| rules.is_published(path))
+    }
+}
+
+fn read_manifest(manifest_path: &Path) -> Result<RawManifest, ManifestError> {
+    let contents = fs::read_to_string(manifest_path)
+        .map_err(|e| ManifestError::Io(manifest_path.to_path_buf(), e))?;
+    toml::from_str(&contents).map_err(|e| ManifestError::Parse(manifest_path.to_path_buf(), e))
+}
+
+/// Finds the `workspace.package` table of the closest workspace manifest above the package.
+fn find_workspace_package(dir: &Path) -> Result<RawWorkspacePackage, ManifestError> {
+    for ancestor in dir.ancestors() {
+        let manifest_path = ancestor.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+        if let Some(workspace) = read_manifest(&manifest_path)?.workspace {
+            return Ok(workspace.package.unwrap_or_default());
+        }
+    }
+
+    Ok(RawWorkspacePackage::default())
+}
+
+fn build_matcher(dir: &Path, patterns: &[String]) -> Result<Gitignore, ignore::Error> {
+    let mut builder = GitignoreBuilder::new(dir);
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    #[test]
+    fn include_and_exclude_rules() {
+        let tree = TempTree::new("packaged");
+        let root = tree.path();
+
+        tree.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"*\"]\n\n[workspace.package]\nexclude = [\"/fixtures\"]\n",
+        );
+        tree.write(
+            "included/Cargo.toml",
+            "[package]\nname = \"included\"\ninclude = [\"/src\", \"!src/skip.rs\"]\nexclude = [\"/src\"]\n",
+        );
+        tree.write(
+            "excluded/Cargo.toml",
+            "[package]\nname = \"excluded\"\nexclude = [\"tests/**/*.snap.rs\", \"benches\"]\n",
+        );
+        tree.write(
+            "inherited/Cargo.toml",
+            "[package]\nname = \"inherited\"\nexclude.workspace = true\n",
+        );
+
+        let mut published = PublishedFiles::default();
+        let mut is_published = |path: &str| published.contains(&root.join(path));
+        let results = [
+            is_published("included/src/lib.rs"),
+            is_published("included/src/nested/mod.rs"),
+            is_published("included/src/skip.rs"),
+            is_published("included/tests/it.rs"),
+            is_published("excluded/src/lib.rs"),
+            is_published("excluded/tests/a/b.snap.rs"),
+            is_published("excluded/benches/bench.rs"),
+            is_published("inherited/src/lib.rs"),
+            is_published("inherited/fixtures/data.rs"),
+            is_published("build.rs"),
+        ];
+
+        assert_eq!(
+            results,
+            [true, true, false, false, true, false, false, true, false, false]
+        );
+    }
+}
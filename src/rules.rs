@@ -0,0 +1,162 @@
+//! User-defined classification of files by path.
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{de, Deserialize, Deserializer};
+
+use crate::warlocs::{Category, InvalidCategory};
+
+/// Ordered rules assigning files matching a glob to a category. The first matching rule wins and
+/// overrides the built-in classification of the file.
+#[derive(Debug, Default, Clone)]
+pub struct CategoryRules {
+    globs: GlobSet,
+    categories: Vec<Category>,
+}
+
+/// Rule given as `GLOB=CATEGORY`, e.g. `**/test_utils.rs=tests`. The category is either a
+/// built-in one or a new category, e.g. `tests/fixtures/**=test-data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryRule {
+    pub glob: String,
+    pub category: Category,
+}
+
+#[derive(Debug)]
+pub enum CategoryRuleError {
+    /// The rule is not in the `GLOB=CATEGORY` form.
+    Syntax(String),
+    Category(InvalidCategory),
+    Glob(globset::Error),
+}
+
+impl Display for CategoryRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax(rule) => {
+                write!(
+                    f,
+                    "invalid category rule `{rule}`, expected `GLOB=CATEGORY`"
+                )
+            }
+            Self::Category(e) => write!(f, "{e}"),
+            Self::Glob(e) => write!(f, "invalid glob in category rule: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CategoryRuleError {}
+
+impl FromStr for CategoryRule {
+    type Err = CategoryRuleError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let (glob, category) = rule
+            .rsplit_once('=')
+            .ok_or_else(|| CategoryRuleError::Syntax(rule.to_string()))?;
+        let (glob, category) = (glob.trim(), category.trim());
+        if glob.is_empty() {
+            return Err(CategoryRuleError::Syntax(rule.to_string()));
+        }
+
+        Ok(Self {
+            glob: glob.to_string(),
+            category: category.parse().map_err(CategoryRuleError::Category)?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for CategoryRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl CategoryRules {
+    pub fn new(rules: impl IntoIterator<Item = CategoryRule>) -> Result<Self, CategoryRuleError> {
+        let mut globs = GlobSetBuilder::new();
+        let mut categories = vec![];
+        for rule in rules {
+            let glob = Glob::new(&rule.glob).map_err(CategoryRuleError::Glob)?;
+            globs.add(glob);
+            categories.push(rule.category);
+        }
+
+        Ok(Self {
+            globs: globs.build().map_err(CategoryRuleError::Glob)?,
+            categories,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    /// Returns the category of the first rule matching the path.
+    pub fn category(&self, path: &Path) -> Option<&Category> {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        self.globs
+            .matches(path)
+            .into_iter()
+            .min()
+            .map(|index| &self.categories[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = CategoryRules::new(
+            [
+                "src/tests_support/** = main",
+                "**/test_utils.rs=tests",
+                "src/**/testing/**=tests",
+                "**/fixtures/**=examples",
+                "tests/data/**=test-data",
+            ]
+            .map(|rule| rule.parse().unwrap()),
+        )
+        .unwrap();
+
+        let category = |path: &str| rules.category(Path::new(path)).cloned();
+        assert_eq!(category("src/test_utils.rs"), Some(Category::Tests));
+        assert_eq!(category("./src/a/test_utils.rs"), Some(Category::Tests));
+        assert_eq!(category("src/a/testing/mod.rs"), Some(Category::Tests));
+        assert_eq!(
+            category("src/tests_support/test_utils.rs"),
+            Some(Category::Main)
+        );
+        assert_eq!(category("tests/fixtures/a.rs"), Some(Category::Examples));
+        assert_eq!(
+            category("tests/data/a.rs"),
+            Some(Category::Custom("test-data".into()))
+        );
+        assert_eq!(category("src/lib.rs"), None);
+
+        assert!(matches!(
+            "tests".parse::<CategoryRule>(),
+            Err(CategoryRuleError::Syntax(_))
+        ));
+        assert_eq!(
+            "a=test data"
+                .parse::<CategoryRule>()
+                .unwrap_err()
+                .to_string(),
+            "invalid category `test data`, expected one of main, tests, examples, benches, \
+             build_scripts, generated or a name made of letters, digits, `-` and `_`"
+        );
+        assert_eq!(
+            "src/lib.rs=path"
+                .parse::<CategoryRule>()
+                .unwrap_err()
+                .to_string(),
+            "invalid category `path`, the name is used by other fields of the serialized output"
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Helpers shared by tests.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Directory of files written by a test in the temporary directory of the system. The directory
+/// is removed with its contents when dropped, even if the test fails.
+pub struct TempTree {
+    root: PathBuf,
+}
+
+impl TempTree {
+    /// Creates an empty directory. `name` must be unique among the tests of the crate, as tests
+    /// run in parallel.
+    pub fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("warloc-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Writes a file at a path relative to the directory, creating its parent directories.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,142 @@
+//! Limits on the lines of code of a category, for failing CI when a project outgrows them.
+
+use std::{collections::BTreeMap, fmt::Display, num::ParseIntError, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::warlocs::{Category, InvalidCategory, Warlocs};
+
+/// Maximum number of lines of code of a category, given as `CATEGORY=LINES`, e.g.
+/// `main=20000`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threshold {
+    pub category: Category,
+    pub max_code: u64,
+}
+
+#[derive(Debug)]
+pub enum ThresholdError {
+    /// The threshold is not in the `CATEGORY=LINES` form.
+    Syntax(String),
+    Category(InvalidCategory),
+    Lines(String, ParseIntError),
+}
+
+impl Display for ThresholdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax(threshold) => {
+                write!(
+                    f,
+                    "invalid threshold `{threshold}`, expected `CATEGORY=LINES`"
+                )
+            }
+            Self::Category(e) => write!(f, "{e}"),
+            Self::Lines(lines, e) => write!(f, "invalid number of lines `{lines}`: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ThresholdError {}
+
+/// Category whose lines of code are above its threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdExceeded {
+    pub category: Category,
+    pub code: u64,
+    pub max_code: u64,
+}
+
+impl Display for ThresholdExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} lines of {} code, above the threshold of {}",
+            self.code, self.category, self.max_code
+        )
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = ThresholdError;
+
+    fn from_str(threshold: &str) -> Result<Self, Self::Err> {
+        let (category, lines) = threshold
+            .split_once('=')
+            .ok_or_else(|| ThresholdError::Syntax(threshold.to_string()))?;
+        let (category, lines) = (category.trim(), lines.trim());
+
+        Ok(Self {
+            category: category.parse().map_err(ThresholdError::Category)?,
+            max_code: lines
+                .parse()
+                .map_err(|e| ThresholdError::Lines(lines.to_string(), e))?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Threshold {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Returns the categories of the stats with more lines of code than their threshold. A later
+/// threshold of a category replaces the earlier ones.
+pub fn check_thresholds(thresholds: &[Threshold], stats: &Warlocs) -> Vec<ThresholdExceeded> {
+    let thresholds: BTreeMap<&Category, u64> = thresholds
+        .iter()
+        .map(|threshold| (&threshold.category, threshold.max_code))
+        .collect();
+
+    thresholds
+        .into_iter()
+        .map(|(category, max_code)| ThresholdExceeded {
+            category: category.clone(),
+            code: stats.get(category).code,
+            max_code,
+        })
+        .filter(|exceeded| exceeded.code > exceeded.max_code)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_thresholds_win() {
+        let mut stats = Warlocs::default();
+        stats.get_mut(Category::Main).code = 100;
+        stats.get_mut(Category::Tests).code = 50;
+        stats.get_mut(Category::Custom("test-data".into())).code = 10;
+        let thresholds = [
+            "main=80",
+            "tests=10",
+            "tests = 50",
+            "test-data=5",
+            "benches=0",
+        ]
+        .map(|threshold| threshold.parse().unwrap());
+
+        let exceeded = check_thresholds(&thresholds, &stats);
+
+        assert_eq!(
+            exceeded.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "100 lines of main code, above the threshold of 80",
+                "10 lines of test-data code, above the threshold of 5",
+            ]
+        );
+        assert!(matches!(
+            "main".parse::<Threshold>(),
+            Err(ThresholdError::Syntax(_))
+        ));
+        assert_eq!(
+            "main=lots".parse::<Threshold>().unwrap_err().to_string(),
+            "invalid number of lines `lots`: invalid digit found in string"
+        );
+    }
+}
@@ -1,29 +1,52 @@
 use std::{
     fs::File,
-    io::{BufReader, Read},
+    io::{self, Read},
     mem,
     path::Path,
 };
 
-use utf8_chars::BufReadCharsExt;
-
-use crate::warlocs::{Locs, Warlocs};
+use crate::{
+    attribute::Attribute,
+    doctests::DocState,
+    generated::{includes_out_dir, GeneratedReason},
+    lexer::{Lexer, Token, TokenKind},
+    warlocs::{Category, FileStats, Locs, Warlocs},
+};
 
-pub struct Visitor<T: Read> {
-    reader: BufReader<T>,
+pub struct Visitor<'a, T: Read> {
+    lexer: Lexer<T>,
+    lookahead: Option<Token>,
     context: VisitorContext,
     stats: Warlocs,
-    lookahead: Option<char>,
+    line_context: LineContext,
+    doc_state: DocState,
     curr_string: String,
     curr_line_no: usize,
-    debug: bool,
+    /// True until the first line of code other than inner attributes, while comments can mark
+    /// the file as generated.
+    in_header: bool,
+    generated: Option<GeneratedReason>,
+    options: &'a VisitorOptions,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum VisitorContext {
+/// Settings controlling how lines are classified.
+#[derive(Debug, Default, Clone)]
+pub struct VisitorOptions {
+    /// Prints out every line with the category it was assigned to.
+    pub debug: bool,
+    /// Paths of attributes marking test functions in addition to
+    /// [`TEST_ATTRIBUTES`](crate::attribute::TEST_ATTRIBUTES).
+    pub test_attributes: Vec<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VisitorContext {
     Main,
     Tests,
     Example,
+    Benches,
+    BuildScript,
+    Generated,
 }
 
 #[derive(Default, Debug, Copy, Clone)]
@@ -31,40 +54,38 @@ struct LineContext {
     has_code: bool,
     has_comment_start: bool,
     has_doc_comment_start: bool,
+    has_doctest: bool,
+    /// Context of the line if it differs from the context of the enclosing block, e.g. for the
+    /// line closing a test module.
+    context: Option<VisitorContext>,
 }
 
-impl LineContext {
-    fn is_inside_comment(&self) -> bool {
-        self.has_comment_start || self.has_doc_comment_start
-    }
+#[derive(Debug, Copy, Clone)]
+enum LineKind {
+    Code,
+    Comment,
+    Doc,
+    DocTest,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-enum Token {
-    LineBreak,
-    WhiteSpace,
-    TestBlockStart,
-    CodeBlockOpen,
-    CodeBlockClose,
-    CommentStart,
-    DocCommentStart,
-    CommentBlockOpen,
-    CommentBlockClose,
-    DocComentBlockOpen,
-    EndOfStatement,
-    DoubleBackSlash,
-    DoubleQuote,
-    EscapedDoubleQuote,
-    StringBlockOpen,
-    StringBlockClose,
-    DoubleStringBlockOpen,
-    DoubleStringBlockClose,
-    Other,
+impl LineContext {
+    fn mark(&mut self, kind: LineKind) {
+        match kind {
+            LineKind::Code => self.has_code = true,
+            LineKind::Comment => self.has_comment_start = true,
+            LineKind::Doc => self.has_doc_comment_start = true,
+            LineKind::DocTest => self.has_doctest = true,
+        }
+    }
 }
 
 impl VisitorContext {
-    fn from_file_path(path: impl AsRef<Path>) -> Self {
-        for component in path.as_ref().components() {
+    /// Classifies a file by the conventional directories in its path, e.g. `tests/`. Only looks
+    /// at the path: build scripts and targets with custom paths are recognized from the
+    /// manifest, see [`ModuleTree`](crate::ModuleTree).
+    pub fn from_file_path(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        for component in path.components() {
             match component {
                 std::path::Component::Normal(os_str)
                     if os_str == "tests" || os_str == "tests.rs" =>
@@ -74,6 +95,9 @@ impl VisitorContext {
                 std::path::Component::Normal(os_str) if os_str == "examples" => {
                     return Self::Example;
                 }
+                std::path::Component::Normal(os_str) if os_str == "benches" => {
+                    return Self::Benches;
+                }
                 _ => {}
             }
         }
@@ -82,394 +106,531 @@ impl VisitorContext {
     }
 }
 
-impl Visitor<File> {
-    pub fn new(file_path: impl AsRef<Path>, debug: bool) -> Self {
-        let file = File::open(&file_path).unwrap_or_else(|e| {
-            panic!(
-                "failed to read file {}: {e}",
-                file_path.as_ref().to_str().unwrap_or_default()
-            )
-        });
-        let mut reader = BufReader::new(file);
+impl<'a> Visitor<'a, File> {
+    /// Creates a visitor of the file at `file_path`, assigned to the context determined from the
+    /// path. Fails if the file cannot be opened.
+    pub fn new(file_path: impl AsRef<Path>, options: &'a VisitorOptions) -> io::Result<Self> {
+        let file = File::open(&file_path)?;
         let context = VisitorContext::from_file_path(file_path);
 
-        let lookahead = reader.chars().next().and_then(|c| c.ok());
+        Ok(Self::from_reader(file, context, options))
+    }
+}
 
+impl<'a, T: Read> Visitor<'a, T> {
+    /// Creates a visitor of source code read from `reader`, assigned to the given context.
+    pub fn from_reader(reader: T, context: VisitorContext, options: &'a VisitorOptions) -> Self {
         Self {
-            reader,
+            lexer: Lexer::new(reader),
+            lookahead: None,
             context,
             stats: Warlocs::default(),
-            lookahead,
+            line_context: LineContext::default(),
+            doc_state: DocState::default(),
             curr_string: String::new(),
             curr_line_no: 1,
-            debug,
+            in_header: true,
+            generated: None,
+            options,
         }
     }
-}
 
-impl<T: Read> Visitor<T> {
-    pub fn visit_file(mut self) -> Warlocs {
+    /// Overrides the context of the file determined from its path.
+    pub fn with_context(mut self, context: VisitorContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn visit_file(mut self) -> FileStats {
         self.visit_code(self.context);
 
-        self.stats
+        if !self.curr_string.is_empty() {
+            // Last line without a trailing line break
+            self.finish_line(self.context);
+        }
+
+        if self.generated.is_some_and(|reason| reason.is_whole_file()) {
+            let total = self.stats.total();
+            self.stats = Warlocs::default();
+            *self.stats.get_mut(Category::Generated) = total;
+        }
+
+        FileStats {
+            stats: self.stats,
+            generated: self.generated,
+        }
     }
 
     fn visit_code(&mut self, context: VisitorContext) {
-        let line_context = LineContext::default();
-        self.visit_code_block(context, line_context, true);
+        self.visit_code_block(context, true);
     }
 
-    fn visit_test_block(&mut self) {
-        self.skip_line(
-            VisitorContext::Tests,
-            LineContext {
-                has_code: true,
-                ..Default::default()
-            },
-        );
-
-        let mut line_context = LineContext::default();
-
+    /// Visits an item marked with a test or bench attribute in the given context up to the end
+    /// of the item: a `;`, the closing brace of its body, or a `,` ending a field, variant or
+    /// match arm. A closing bracket of the enclosing block also ends the item and is left for the
+    /// caller.
+    fn visit_item_block(&mut self, context: VisitorContext) {
+        self.line_context.has_code = true;
+
+        let mut depth = 0usize;
+        // Depth of generic arguments, e.g. `HashMap<K, V>`, which contain commas too
+        let mut angle_depth = 0usize;
+        // Items like functions and impls can have commas in `where` clauses, only fields,
+        // variants and match arms end with a comma
+        let mut is_declaration = false;
+        // A `let` statement ends at its semicolon, after any block of its initializer
+        let mut is_statement = false;
+        let mut prev_punct = None;
+        // Generic arguments directly follow a path segment or `::`, unlike a comparison
+        let mut after_path = false;
         while let Some(token) = self.next_token() {
-            match token {
-                Token::LineBreak => {
-                    self.finish_line(VisitorContext::Tests, line_context);
-                    line_context = LineContext::default();
+            let punct = match token.kind {
+                TokenKind::Punct(c) => Some(c),
+                _ => None,
+            };
+            let is_trivia = token.kind.is_trivia();
+            let is_path = matches!(token.kind, TokenKind::Ident | TokenKind::Punct(':'));
+            match token.kind {
+                TokenKind::Punct('(' | '[') => {
+                    self.line_context.has_code = true;
+                    depth += 1;
+                }
+                TokenKind::Punct(')' | ']' | '}') if depth == 0 => {
+                    self.lookahead = Some(token);
+                    return;
                 }
-                Token::EndOfStatement => {
-                    line_context.has_code = true;
-                    self.skip_line(VisitorContext::Tests, line_context);
+                TokenKind::Punct(')' | ']') => {
+                    self.line_context.has_code = true;
+                    depth -= 1;
+                }
+                TokenKind::Punct('<') if after_path => {
+                    self.line_context.has_code = true;
+                    angle_depth += 1;
+                }
+                // The pattern and guard of a match arm end at its arrow
+                TokenKind::Punct('>') if depth == 0 && prev_punct == Some('=') => {
+                    self.line_context.has_code = true;
+                    angle_depth = 0;
+                }
+                // Arrows of return types and match arms are not closing brackets
+                TokenKind::Punct('>') if !matches!(prev_punct, Some('-' | '=')) => {
+                    self.line_context.has_code = true;
+                    angle_depth = angle_depth.saturating_sub(1);
+                }
+                TokenKind::Punct(';') if depth == 0 => {
+                    self.line_context.has_code = true;
+                    self.line_context.context = Some(context);
                     return;
                 }
-                Token::CodeBlockOpen => {
-                    self.visit_code_block(VisitorContext::Tests, line_context, false);
-                    line_context.has_code = true;
-                    self.skip_line(VisitorContext::Tests, line_context);
+                TokenKind::Punct(',') if depth == 0 && angle_depth == 0 && !is_declaration => {
+                    self.line_context.has_code = true;
+                    self.line_context.context = Some(context);
                     return;
                 }
-                Token::WhiteSpace => {}
-                _ => {
-                    if !line_context.is_inside_comment() {
-                        line_context.has_code = true;
-                    }
+                TokenKind::Punct('{') if depth == 0 && is_statement => {
+                    self.line_context.has_code = true;
+                    self.visit_code_block(context, false);
                 }
+                TokenKind::Punct('{') if depth == 0 => {
+                    self.line_context.has_code = true;
+                    self.visit_code_block(context, false);
+                    self.line_context.context = Some(context);
+                    return;
+                }
+                TokenKind::Punct('#') => {
+                    self.visit_attribute(context, true);
+                }
+                TokenKind::Ident if token.text == "let" && depth == 0 && !is_declaration => {
+                    is_statement = true;
+                    self.visit_token(context, token);
+                }
+                TokenKind::Ident if is_item_keyword(&token.text) => {
+                    is_declaration = true;
+                    self.visit_token(context, token);
+                }
+                _ => self.visit_token(context, token),
             }
-        }
-    }
-
-    fn skip_line(&mut self, context: VisitorContext, line_context: LineContext) {
-        while let Some(char) = self.next_char() {
-            if char == '\n' {
-                break;
+            if !is_trivia {
+                prev_punct = punct;
             }
+            after_path = is_path;
         }
-
-        self.finish_line(context, line_context);
     }
 
-    fn visit_code_block(
-        &mut self,
-        context: VisitorContext,
-        line_context: LineContext,
-        till_the_end: bool,
-    ) {
-        let mut line_context = line_context;
+    fn visit_code_block(&mut self, context: VisitorContext, till_the_end: bool) {
+        let outer_context = context;
+        let mut context = context;
+        let scope_start = *self.mut_stats(context);
+
         while let Some(token) = self.next_token() {
-            match token {
-                Token::LineBreak => {
-                    self.finish_line(context, line_context);
-                    line_context = LineContext::default();
+            match token.kind {
+                TokenKind::Punct('{') => {
+                    self.line_context.has_code = true;
+                    self.visit_code_block(context, false);
                 }
-                Token::WhiteSpace => {}
-                Token::CommentStart => {
-                    line_context.has_comment_start = true;
-                    self.skip_line(context, line_context);
-                    line_context = LineContext::default();
-                }
-                Token::DocCommentStart => {
-                    line_context.has_doc_comment_start = true;
-                    self.skip_line(context, line_context);
-                    line_context = LineContext::default();
-                }
-                Token::CommentBlockOpen => {
-                    self.visit_comment_block(context, false);
-                    line_context.has_comment_start = true;
-                }
-                Token::DocComentBlockOpen => {
-                    self.visit_comment_block(context, true);
-                    line_context.has_doc_comment_start = true;
-                }
-                Token::TestBlockStart => {
-                    self.visit_test_block();
-                }
-                Token::CodeBlockOpen => {
-                    self.visit_code_block(context, line_context, false);
-                    line_context.has_code = true;
-                }
-                Token::CodeBlockClose => {
+                TokenKind::Punct('}') => {
+                    self.line_context.has_code = true;
                     if !till_the_end {
+                        if context != outer_context {
+                            self.line_context.context = Some(context);
+                        }
                         return;
                     }
                 }
-                Token::DoubleQuote => {
-                    self.visit_string(context);
-                    line_context.has_code = true;
-                }
-                Token::StringBlockOpen => {
-                    self.visit_string_block(context, Token::StringBlockClose);
-                    line_context.has_code = true;
+                TokenKind::Ident if token.text == "include" => {
+                    self.visit_token(context, token);
+                    self.visit_include(context);
                 }
-                Token::DoubleStringBlockOpen => {
-                    self.visit_string_block(context, Token::DoubleStringBlockClose);
-                    line_context.has_code = true;
+                TokenKind::Punct('#') => {
+                    if self.visit_attribute(context, false) && context != VisitorContext::Tests {
+                        // The whole scope is test code, including the lines already visited
+                        let scope_stats = *self.mut_stats(context) - scope_start;
+                        *self.mut_stats(context) -= scope_stats;
+                        *self.mut_stats(VisitorContext::Tests) += scope_stats;
+                        context = VisitorContext::Tests;
+                    }
                 }
-                _ => line_context.has_code = true,
+                _ => self.visit_token(context, token),
             }
         }
     }
 
-    fn visit_string_block(&mut self, context: VisitorContext, closing_token: Token) {
-        let mut line_context = LineContext {
-            has_code: true,
-            has_comment_start: false,
-            has_doc_comment_start: false,
-        };
-
-        while let Some(token) = self.next_token() {
-            match token {
-                Token::LineBreak => {
-                    self.finish_line(context, line_context);
-                    line_context = LineContext::default();
+    /// Visits a possible `include!` macro call after the `include` identifier. Lines of the call
+    /// are generated code if it includes a file from `OUT_DIR`.
+    fn visit_include(&mut self, context: VisitorContext) {
+        let start = *self.mut_stats(context);
+
+        let mut is_macro = false;
+        loop {
+            let Some(token) = self.next_token() else {
+                return;
+            };
+
+            match token.kind {
+                TokenKind::Punct('(' | '[' | '{') if is_macro => {
+                    self.visit_token(context, token);
+                    break;
                 }
-                v if v == closing_token => {
+                TokenKind::Punct('!') if !is_macro => {
+                    is_macro = true;
+                    self.visit_token(context, token);
+                }
+                kind if kind.is_trivia() => self.visit_token(context, token),
+                _ => {
+                    self.lookahead = Some(token);
                     return;
                 }
-                _ => line_context.has_code = true,
             }
         }
+
+        let mut args = vec![];
+        let mut depth = 1usize;
+        while let Some(token) = self.next_token() {
+            match token.kind {
+                TokenKind::Punct('(' | '[' | '{') => depth += 1,
+                TokenKind::Punct(')' | ']' | '}') => depth -= 1,
+                _ => {}
+            }
+
+            if depth == 0 {
+                self.visit_token(context, token);
+                break;
+            }
+
+            if !token.kind.is_trivia() {
+                args.push(token.clone());
+            }
+            self.visit_token(context, token);
+        }
+
+        if includes_out_dir(&args) {
+            let include_stats = *self.mut_stats(context) - start;
+            *self.mut_stats(context) -= include_stats;
+            *self.mut_stats(VisitorContext::Generated) += include_stats;
+            self.line_context.context = Some(VisitorContext::Generated);
+            self.generated.get_or_insert(GeneratedReason::OutDirInclude);
+        }
     }
 
-    fn visit_string(&mut self, context: VisitorContext) {
-        let mut line_context = LineContext {
-            has_code: true,
-            has_comment_start: false,
-            has_doc_comment_start: false,
-        };
+    /// Visits an attribute after its leading `#`. Items marked with an outer test or bench
+    /// attribute are visited as test or bench code, unless the attribute is one of several on an
+    /// item that is already being visited (`in_item`), e.g. stacked `#[test_case]` attributes.
+    ///
+    /// Returns true for an inner test attribute (like `#![cfg(test)]`), which makes the whole
+    /// enclosing scope test code.
+    fn visit_attribute(&mut self, context: VisitorContext, in_item: bool) -> bool {
+        self.line_context.has_code = true;
+        let in_header = self.in_header;
+
+        let mut is_inner = false;
+        loop {
+            let Some(token) = self.next_token() else {
+                return false;
+            };
+
+            match token.kind {
+                TokenKind::Punct('[') => break,
+                TokenKind::Punct('!') if !is_inner => is_inner = true,
+                kind if kind.is_trivia() => self.visit_token(context, token),
+                _ => {
+                    self.lookahead = Some(token);
+                    return false;
+                }
+            }
+        }
 
+        let mut tokens = vec![];
+        let mut depth = 1usize;
         while let Some(token) = self.next_token() {
-            match token {
-                Token::LineBreak => {
-                    self.finish_line(context, line_context);
-                    line_context = LineContext::default();
-                }
-                Token::DoubleQuote => return,
-                _ => line_context.has_code = true,
+            match token.kind {
+                TokenKind::Punct('[') => depth += 1,
+                TokenKind::Punct(']') => depth -= 1,
+                _ => {}
             }
+
+            if depth == 0 {
+                self.visit_token(context, token);
+                break;
+            }
+
+            if !token.kind.is_trivia() {
+                tokens.push(token.clone());
+            }
+            self.visit_token(context, token);
         }
-    }
 
-    fn visit_comment_block(&mut self, context: VisitorContext, is_doc: bool) {
-        let mut line_context = LineContext {
-            has_code: false,
-            has_comment_start: !is_doc,
-            has_doc_comment_start: is_doc,
+        // Inner attributes like `#![allow(clippy::all)]` can precede the header comment
+        self.in_header = in_header && is_inner;
+
+        let Some(attribute) = Attribute::parse(&tokens) else {
+            return false;
         };
+        let is_test = attribute.is_test(&self.options.test_attributes);
+        if !is_inner && !in_item {
+            if is_test {
+                self.visit_item_block(VisitorContext::Tests);
+            } else if attribute.is_bench() {
+                self.visit_item_block(VisitorContext::Benches);
+            }
+        }
 
-        while let Some(token) = self.next_token() {
-            match token {
-                Token::LineBreak => {
-                    self.finish_line(context, line_context);
-                    line_context = LineContext::default();
-                }
-                Token::CommentBlockOpen => {
-                    self.visit_comment_block(context, false);
-                }
-                Token::CommentBlockClose => {
-                    return;
-                }
-                Token::DocComentBlockOpen => {
-                    self.visit_comment_block(context, true);
+        is_test && is_inner
+    }
+
+    /// Visits any token that does not change the structure of the code.
+    fn visit_token(&mut self, context: VisitorContext, token: Token) {
+        match token.kind {
+            TokenKind::LineBreak => self.finish_line(context),
+            TokenKind::WhiteSpace => {}
+            TokenKind::Shebang => self.line_context.has_comment_start = true,
+            TokenKind::LineComment { doc_style: None } => {
+                self.visit_header_comment(&token);
+                self.line_context.has_comment_start = true
+            }
+            TokenKind::LineComment { doc_style: Some(_) } => {
+                let kind = self.doc_line_kind(&token.text[3..]);
+                self.line_context.mark(kind);
+            }
+            TokenKind::BlockComment { doc_style, .. } => {
+                let kind = match doc_style {
+                    Some(_) => LineKind::Doc,
+                    None => {
+                        self.visit_header_comment(&token);
+                        LineKind::Comment
+                    }
+                };
+                self.visit_multiline_token(context, &token, kind);
+            }
+            TokenKind::Literal { .. } => {
+                self.in_header = false;
+                self.visit_multiline_token(context, &token, LineKind::Code)
+            }
+            // tonic-build appends services to the output of prost-build, under the same header
+            TokenKind::Ident if token.text == "tonic" => {
+                if self.generated == Some(GeneratedReason::Prost) {
+                    self.generated = Some(GeneratedReason::Tonic);
                 }
-                Token::WhiteSpace => {}
-                _ => {
-                    line_context.has_comment_start = !is_doc;
-                    line_context.has_doc_comment_start = is_doc;
+                self.in_header = false;
+                self.line_context.has_code = true
+            }
+            _ => {
+                self.in_header = false;
+                self.line_context.has_code = true
+            }
+        }
+    }
+
+    /// Marks the whole file as generated if a comment in its header says so. Only regular
+    /// comments are checked: documentation describes the code rather than how it was written.
+    fn visit_header_comment(&mut self, token: &Token) {
+        if self.in_header && self.generated.is_none() {
+            self.generated = GeneratedReason::from_header_comment(&token.text);
+        }
+    }
+
+    /// Assigns every line a token spans to the given kind of line. Lines inside the token that
+    /// contain only whitespace are counted as blank. Lines of documentation can also be
+    /// assigned to doctests.
+    fn visit_multiline_token(&mut self, context: VisitorContext, token: &Token, kind: LineKind) {
+        let segments_count = token.text.split('\n').count();
+        for (i, segment) in token.text.split('\n').enumerate() {
+            if i > 0 {
+                self.curr_string.push('\n');
+                self.finish_line(context);
+            }
+
+            // Text of single line tokens is added in `next_token`
+            if segments_count > 1 {
+                self.curr_string.push_str(segment);
+            }
+            if segment.trim().is_empty() {
+                continue;
+            }
+
+            match kind {
+                LineKind::Doc => {
+                    let mut content = segment;
+                    if i == 0 {
+                        content = &content[3..];
+                    } else {
+                        content = content.trim_start();
+                        if !content.starts_with("*/") {
+                            content = content.strip_prefix('*').unwrap_or(content);
+                        }
+                    }
+                    if i == segments_count - 1 {
+                        content = content.strip_suffix("*/").unwrap_or(content);
+                    }
+
+                    let kind = self.doc_line_kind(content);
+                    self.line_context.mark(kind);
                 }
+                kind => self.line_context.mark(kind),
             }
         }
     }
 
-    fn finish_line(&mut self, context: VisitorContext, line_context: LineContext) {
-        let curr = std::mem::take(&mut self.curr_string);
+    /// Returns the kind of a documentation line with the comment markers stripped.
+    fn doc_line_kind(&mut self, content: &str) -> LineKind {
+        if self.doc_state.is_doctest_line(content) {
+            LineKind::DocTest
+        } else {
+            LineKind::Doc
+        }
+    }
+
+    fn finish_line(&mut self, context: VisitorContext) {
+        let curr = mem::take(&mut self.curr_string);
+        let line_context = mem::take(&mut self.line_context);
         let line = self.curr_line_no;
         self.curr_line_no += 1;
 
-        let stats = self.mut_stats(context);
+        let stats = self.mut_stats(line_context.context.unwrap_or(context));
 
         if line_context.has_code {
             stats.code += 1;
+            self.doc_state.reset();
 
-            if self.debug {
+            if self.options.debug {
                 eprint!("{line}: CODE: {curr}");
             }
+        } else if line_context.has_doctest {
+            stats.doctests += 1;
+            if self.options.debug {
+                eprint!("{line}: DTST: {curr}");
+            }
         } else if line_context.has_doc_comment_start {
             stats.docs += 1;
-            if self.debug {
+            if self.options.debug {
                 eprint!("{line}: DOCS: {curr}");
             }
         } else if line_context.has_comment_start {
             stats.comments += 1;
-            if self.debug {
+            if self.options.debug {
                 eprint!("{line}: COMM: {curr}");
             }
         } else {
             stats.whitespaces += 1;
-            if self.debug {
+            if self.options.debug {
                 eprint!("{line}: WHITE: {curr}");
             }
         }
     }
 
     fn mut_stats(&mut self, context: VisitorContext) -> &mut Locs {
-        match context {
-            VisitorContext::Main => &mut self.stats.main,
-            VisitorContext::Tests => &mut self.stats.tests,
-            VisitorContext::Example => &mut self.stats.examples,
-        }
+        self.stats.get_mut(context.into())
     }
 
     fn next_token(&mut self) -> Option<Token> {
-        let next_char = self.next_char()?;
-        let token = match next_char {
-            '\n' => Token::LineBreak,
-            '/' if self.lookahead == Some('/') => {
-                let _ = self.next_char();
-                if self.lookahead == Some('/') || self.lookahead == Some('!') {
-                    let next_char = self.next_char()?;
-                    if next_char == '/' && self.lookahead == Some('/') {
-                        Token::CommentStart
-                    } else {
-                        Token::DocCommentStart
-                    }
-                } else {
-                    Token::CommentStart
-                }
-            }
-            '/' if self.lookahead == Some('*') => {
-                let mut string = '/'.to_string();
-                self.collect_while(&mut string, |c| c == '!' || c == '*' || c == '/');
-                match string.as_str() {
-                    "/**" | "/*!" => Token::DocComentBlockOpen,
-                    v if v.ends_with("*/") => Token::WhiteSpace,
-                    _ => Token::CommentBlockOpen,
-                }
-            }
-            '*' if self.lookahead == Some('/') => {
-                let _ = self.next_char();
-                Token::CommentBlockClose
-            }
-            '#' if self.lookahead == Some('[') => {
-                let mut string = '#'.to_string();
-                self.collect_while(&mut string, |c| c != ']' && c != '\n');
-
-                if let Some(next) = self.lookahead {
-                    match next {
-                        ']' => {
-                            let _ = self.next_char();
-                            string.push(next)
-                        }
-                        _ => return Some(Token::Other),
-                    }
-                }
+        if let Some(token) = self.lookahead.take() {
+            return Some(token);
+        }
 
-                match string.as_str() {
-                    "#[cfg(test)]" | "#[test]" => Token::TestBlockStart,
-                    _ => Token::Other,
-                }
-            }
-            '{' => Token::CodeBlockOpen,
-            '}' => Token::CodeBlockClose,
-            ';' => Token::EndOfStatement,
-            '\\' if self.lookahead == Some('\\') => {
-                let _ = self.next_char();
-                Token::DoubleBackSlash
-            }
-            '\\' if self.lookahead == Some('"') => {
-                let _ = self.next_char();
-                Token::EscapedDoubleQuote
-            }
-            '"' if self.lookahead == Some('#') => {
-                let mut string = '"'.to_string();
-                self.collect_while(&mut string, |c| c == '#');
-                match string.as_ref() {
-                    "\"#" => Token::StringBlockClose,
-                    "\"##" => Token::DoubleStringBlockClose,
-                    _ => Token::Other,
-                }
-            }
-            '"' => Token::DoubleQuote,
-            'r' if self.lookahead == Some('#') => {
-                let mut string = 'r'.to_string();
-                self.collect_while(&mut string, |c| c == '#' || c == '"');
-                match string.as_ref() {
-                    "r#\"" => Token::StringBlockOpen,
-                    "r##\"" => Token::DoubleStringBlockOpen,
-                    _ => Token::Other,
-                }
-            }
-            v if v.is_whitespace() => Token::WhiteSpace,
-            _ => Token::Other,
-        };
+        let token = self.lexer.next_token()?;
+        // Text of multiline tokens is added line by line in `visit_multiline_token`
+        if token.kind == TokenKind::LineBreak || !token.text.contains('\n') {
+            self.curr_string.push_str(&token.text);
+        }
 
         Some(token)
     }
+}
 
-    fn collect_while(&mut self, string: &mut String, mut predicate: impl FnMut(char) -> bool) {
-        while let Some(next_char) = self.lookahead {
-            if predicate(next_char) {
-                let _ = self.next_char();
-                string.push(next_char);
-            } else {
-                break;
-            }
-        }
-    }
-
-    fn next_char(&mut self) -> Option<char> {
-        use utf8_chars::BufReadCharsExt;
-
-        let c = mem::replace(
-            &mut self.lookahead,
-            self.reader.chars().next().and_then(|c| c.ok()),
-        );
-
-        if let Some(c) = c {
-            self.curr_string.push(c);
-        }
-        c
-    }
+/// Returns true for keywords starting an item declaration, e.g. `fn` or `impl`.
+fn is_item_keyword(ident: &str) -> bool {
+    matches!(
+        ident,
+        "fn" | "impl"
+            | "struct"
+            | "enum"
+            | "union"
+            | "trait"
+            | "mod"
+            | "type"
+            | "const"
+            | "static"
+            | "use"
+            | "extern"
+            | "macro_rules"
+    )
 }
 
 #[cfg(test)]
 mod tests {
-    use utf8_chars::BufReadCharsExt;
-
     use super::*;
 
     fn stats(file: &str) -> Warlocs {
-        let mut reader = BufReader::new(file.as_bytes());
-        let lookahead = reader.chars().next().and_then(|c| c.ok());
+        stats_with_options(
+            file,
+            &VisitorOptions {
+                debug: true,
+                ..Default::default()
+            },
+        )
+    }
 
-        Visitor {
-            reader,
-            context: VisitorContext::Main,
-            stats: Warlocs::default(),
-            lookahead,
-            curr_string: String::new(),
-            curr_line_no: 1,
-            debug: true,
+    fn stats_with_options(file: &str, options: &VisitorOptions) -> Warlocs {
+        Visitor::from_reader(file.as_bytes(), VisitorContext::Main, options)
+            .visit_file()
+            .stats
+    }
+
+    #[test]
+    fn debug_line_text() {
+        let file = "let s = \"/* {\"; /* a */\nlet t = r\"\n\";\n";
+        let options = VisitorOptions::default();
+        let mut visitor = Visitor::from_reader(file.as_bytes(), VisitorContext::Main, &options);
+
+        // Text of the line when its line break is reached
+        let mut lines = vec![];
+        while let Some(token) = visitor.next_token() {
+            if token.kind == TokenKind::LineBreak {
+                lines.push(visitor.curr_string.clone());
+            }
+            visitor.visit_token(VisitorContext::Main, token);
         }
-        .visit_file()
+
+        assert_eq!(lines, ["let s = \"/* {\"; /* a */\n", "\";\n"]);
     }
 
     #[test]
@@ -477,8 +638,8 @@ mod tests {
         let file = "\n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.whitespaces, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -486,8 +647,8 @@ mod tests {
         let file = "  \t\t \n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.whitespaces, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -495,8 +656,8 @@ mod tests {
         let file = "mod lib;\n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.code, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].code, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -504,8 +665,8 @@ mod tests {
         let file = "   // Comment\n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.comments, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].comments, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -513,8 +674,8 @@ mod tests {
         let file = "   /// Documentation\n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.docs, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].docs, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -522,8 +683,8 @@ mod tests {
         let file = "   //! Documentation\n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.docs, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].docs, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -531,8 +692,8 @@ mod tests {
         let file = "   /* comment */ \n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.comments, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].comments, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -545,9 +706,9 @@ mod tests {
 
         let stats = stats(file);
 
-        assert_eq!(stats.main.comments, 3);
-        assert_eq!(stats.main.whitespaces, 1);
-        assert_eq!(stats.main.sum(), 4);
+        assert_eq!(stats[Category::Main].comments, 3);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+        assert_eq!(stats[Category::Main].sum(), 4);
     }
 
     #[test]
@@ -555,8 +716,8 @@ mod tests {
         let file = "   /** comment */ \n";
         let stats = stats(file);
 
-        assert_eq!(stats.main.docs, 1);
-        assert_eq!(stats.main.sum(), 1);
+        assert_eq!(stats[Category::Main].docs, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
     }
 
     #[test]
@@ -569,9 +730,9 @@ mod tests {
 
         let stats = stats(file);
 
-        assert_eq!(stats.main.docs, 3);
-        assert_eq!(stats.main.whitespaces, 1);
-        assert_eq!(stats.main.sum(), 4);
+        assert_eq!(stats[Category::Main].docs, 3);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+        assert_eq!(stats[Category::Main].sum(), 4);
     }
 
     #[test]
@@ -583,8 +744,20 @@ let a = 1;
 
         let stats = stats(file);
 
-        assert_eq!(stats.main.comments, 0);
-        assert_eq!(stats.main.code, 2);
+        assert_eq!(stats[Category::Main].comments, 0);
+        assert_eq!(stats[Category::Main].code, 2);
+    }
+
+    #[test]
+    fn context_from_path() {
+        let context = VisitorContext::from_file_path;
+        assert_eq!(context("src/lib.rs"), VisitorContext::Main);
+        assert_eq!(context("tests/it.rs"), VisitorContext::Tests);
+        assert_eq!(context("src/tests.rs"), VisitorContext::Tests);
+        assert_eq!(context("examples/demo/main.rs"), VisitorContext::Example);
+        assert_eq!(context("benches/bench.rs"), VisitorContext::Benches);
+        // Build scripts are only known from the manifest, even next to an existing `Cargo.toml`
+        assert_eq!(context("build.rs"), VisitorContext::Main);
     }
 
     #[test]
@@ -600,9 +773,9 @@ mod tests {
 
         let stats = stats(file);
 
-        assert_eq!(stats.tests.code, 4);
-        assert_eq!(stats.tests.whitespaces, 2);
-        assert_eq!(stats.tests.sum(), 6);
+        assert_eq!(stats[Category::Tests].code, 4);
+        assert_eq!(stats[Category::Tests].whitespaces, 2);
+        assert_eq!(stats[Category::Tests].sum(), 6);
     }
 
     #[test]
@@ -619,8 +792,497 @@ This is a string
 
         let stats = stats(file);
 
-        assert_eq!(stats.main.code, 4);
-        assert_eq!(stats.main.whitespaces, 4);
-        assert_eq!(stats.main.sum(), 8);
+        assert_eq!(stats[Category::Main].code, 4);
+        assert_eq!(stats[Category::Main].whitespaces, 4);
+        assert_eq!(stats[Category::Main].sum(), 8);
+    }
+
+    #[test]
+    fn raw_string_literals_with_many_hashes() {
+        let file = r####"
+let sql = r###"
+SELECT "#" FROM t -- "## is not the end
+/* not a comment */ {
+"###;
+let a = 1;
+"####;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 5);
+        assert_eq!(stats[Category::Main].comments, 0);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+    }
+
+    #[test]
+    fn prefixed_string_literals() {
+        let file = r####"
+let a = b"// {";
+let b = br#"
+/* "# { "#;
+let c = c"// }";
+let d = cr##"
+// "#
+"##;
+let e = r"\";
+// Comment
+"####;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 8);
+        assert_eq!(stats[Category::Main].comments, 1);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+    }
+
+    #[test]
+    fn multiline_prefixed_string_literals_in_test_block() {
+        let file = r####"
+#[cfg(test)]
+mod tests {
+    const HTML: &[u8] = br##"
+
+<a href="#">}</a>
+"##;
+}
+fn main() {}
+"####;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Tests].code, 6);
+        assert_eq!(stats[Category::Tests].whitespaces, 1);
+        assert_eq!(stats[Category::Main].code, 1);
+    }
+
+    #[test]
+    fn char_literals_do_not_open_blocks() {
+        let file = r#"
+fn brackets(c: char) -> bool {
+    matches!(c, '{' | '"' | '[')
+}
+
+#[cfg(test)]
+mod tests {
+    fn quote() -> char {
+        '\''
+    }
+}
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 3);
+        assert_eq!(stats[Category::Main].whitespaces, 2);
+        assert_eq!(stats[Category::Tests].code, 6);
+    }
+
+    #[test]
+    fn lifetimes_and_byte_literals() {
+        let file = r#"
+fn first<'a>(s: &'a str) -> &'a [u8] {
+    let _ = (b'"', b"{", br"}");
+    s.as_bytes()
+}
+// Comment
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 4);
+        assert_eq!(stats[Category::Main].comments, 1);
+        assert_eq!(stats[Category::Main].whitespaces, 1);
+    }
+
+    #[test]
+    fn escaped_quotes_in_strings() {
+        let file = r#"
+let a = "\" // not a comment {";
+let b = "\\"; // Comment
+let c = 1;
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 3);
+        assert_eq!(stats[Category::Main].comments, 0);
+        assert_eq!(stats[Category::Main].sum(), 4);
+    }
+
+    #[test]
+    fn nested_comment_block() {
+        let file = r#"/* outer
+/* inner */
+still a comment */
+let a = 1;
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].comments, 3);
+        assert_eq!(stats[Category::Main].code, 1);
+    }
+
+    #[test]
+    fn shebang() {
+        let file = "#!/usr/bin/env rust-script\nfn main() {}\n";
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].comments, 1);
+        assert_eq!(stats[Category::Main].code, 1);
+    }
+
+    #[test]
+    fn last_line_without_line_break() {
+        let file = "fn main() {}";
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 1);
+        assert_eq!(stats[Category::Main].sum(), 1);
+    }
+
+    #[test]
+    fn cfg_predicates_with_test() {
+        let file = r#"
+#[cfg(all(test, feature = "x"))]
+mod a {}
+# [cfg (test)]
+mod b {}
+#[cfg(any(test, fuzzing))]
+mod c {}
+#[cfg(not(test))]
+mod d {}
+"#;
+
+        // `c` is compiled without `test` too, in builds with `fuzzing`
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Tests].code, 4);
+        assert_eq!(stats[Category::Main].code, 4);
+    }
+
+    #[test]
+    fn test_framework_attributes() {
+        let file = r#"
+#[tokio::test]
+async fn a() {}
+#[rstest]
+#[case(1)]
+fn b(#[case] x: u32) {}
+#[test_case(1 ; "one")]
+fn c(x: u32) {}
+#[my_framework::check]
+fn d() {}
+"#;
+
+        let stats = stats(file);
+        assert_eq!(stats[Category::Tests].code, 7);
+        assert_eq!(stats[Category::Main].code, 2);
+
+        let options = VisitorOptions {
+            test_attributes: vec!["my_framework::check".to_string()],
+            ..Default::default()
+        };
+        let stats = stats_with_options(file, &options);
+        assert_eq!(stats[Category::Tests].code, 9);
+        assert_eq!(stats[Category::Main].code, 0);
+    }
+
+    #[test]
+    fn stacked_test_attributes() {
+        let file = r#"
+#[test_case(1)]
+#[test_case(2)]
+fn c(x: u32) {}
+#[cfg(test)]
+#[test]
+fn d() {}
+fn main() {
+}
+"#;
+
+        let stats = stats(file);
+        assert_eq!(stats[Category::Tests].code, 6);
+        assert_eq!(stats[Category::Main].code, 2);
+    }
+
+    #[test]
+    fn test_attributes_on_fields_variants_and_arms() {
+        let file = r#"
+pub struct Config {
+    #[cfg(test)]
+    pub fake_clock: HashMap<u32, bool>,
+    pub name: String,
+}
+
+enum Clock {
+    #[cfg(test)]
+    Fake(u64),
+    System,
+}
+
+impl Config {
+    fn clock(&self) -> Clock {
+        match self.fake_clock {
+            #[cfg(test)]
+            Some(time) => Clock::Fake(time),
+            _ => Clock::System,
+        }
+    }
+}
+"#;
+
+        let stats = stats(file);
+        assert_eq!(stats[Category::Tests].code, 6);
+        assert_eq!(stats[Category::Main].code, 13);
+    }
+
+    #[test]
+    fn test_attributes_on_guards_and_statements() {
+        let file = r#"
+enum Flags {
+    #[cfg(test)]
+    Fake = 1 << 2,
+    System = 1,
+}
+
+fn f(x: Option<u32>, a: bool) -> u32 {
+    #[cfg(test)]
+    let y = if a {
+        1
+    } else {
+        2
+    };
+    match x {
+        #[cfg(test)]
+        Some(y) if y < 3 => 1,
+        Some(y) if y > 5 => 2,
+        _ => 0,
+    }
+}
+"#;
+
+        let stats = stats(file);
+        assert_eq!(stats[Category::Tests].code, 10);
+        assert_eq!(stats[Category::Main].code, 9);
+    }
+
+    #[test]
+    fn inner_test_attribute_in_file() {
+        let file = r#"//! Test helpers
+#![cfg(test)]
+
+pub fn helper() {}
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Tests].docs, 1);
+        assert_eq!(stats[Category::Tests].code, 2);
+        assert_eq!(stats[Category::Tests].whitespaces, 1);
+        assert_eq!(stats[Category::Main].sum(), 0);
+    }
+
+    #[test]
+    fn inner_test_attribute_in_module() {
+        let file = r#"
+fn main() {}
+
+mod helpers {
+    #![cfg(all(test, unix))]
+
+    fn helper() {}
+}
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].code, 1);
+        assert_eq!(stats[Category::Main].whitespaces, 2);
+        assert_eq!(stats[Category::Tests].code, 4);
+        assert_eq!(stats[Category::Tests].whitespaces, 1);
+    }
+
+    #[test]
+    fn doctests() {
+        let file = r#"
+/// Adds one.
+///
+/// ```
+/// assert_eq!(add_one(1), 2);
+/// ```
+///
+/// ```text
+/// add_one: i32 -> i32
+/// ```
+fn add_one(x: i32) -> i32 {
+    x + 1
+}
+
+/**
+ * ```no_run
+ * main();
+ * ```
+ */
+fn main() {}
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Main].doctests, 6);
+        assert_eq!(stats[Category::Main].docs, 8);
+        assert_eq!(stats[Category::Main].code, 4);
+    }
+
+    #[test]
+    fn bench_attribute() {
+        let file = r#"
+#[bench]
+fn bench_add(b: &mut Bencher) {
+    b.iter(|| 1 + 1);
+}
+"#;
+
+        let stats = stats(file);
+
+        assert_eq!(stats[Category::Benches].code, 4);
+        assert_eq!(stats[Category::Main].sum(), 1);
+    }
+
+    #[test]
+    fn generated_files() {
+        let file = r#"// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HelloRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+
+#[cfg(test)]
+mod tests {}
+"#;
+
+        let FileStats {
+            stats,
+            generated: reason,
+        } = Visitor::from_reader(
+            file.as_bytes(),
+            VisitorContext::Main,
+            &VisitorOptions::default(),
+        )
+        .visit_file();
+
+        assert_eq!(reason, Some(GeneratedReason::Prost));
+        assert_eq!(stats[Category::Generated].code, 7);
+        assert_eq!(stats[Category::Generated].comments, 1);
+        assert_eq!(stats[Category::Generated].whitespaces, 1);
+        assert_eq!(
+            stats[Category::Main].sum() + stats[Category::Tests].sum(),
+            0
+        );
+    }
+
+    #[test]
+    fn tonic_output() {
+        let file = r#"// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HelloRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+/// Generated client implementations.
+pub mod greeter_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+}
+"#;
+
+        let FileStats {
+            stats,
+            generated: reason,
+        } = Visitor::from_reader(
+            file.as_bytes(),
+            VisitorContext::Main,
+            &VisitorOptions::default(),
+        )
+        .visit_file();
+
+        assert_eq!(reason, Some(GeneratedReason::Tonic));
+        assert_eq!(stats[Category::Generated].code, 16);
+        assert_eq!(stats[Category::Generated].docs, 1);
+        assert_eq!(stats[Category::Main].sum(), 0);
+    }
+
+    #[test]
+    fn generated_header_after_code() {
+        let file = r#"#![allow(clippy::all)]
+/* automatically generated by rust-bindgen 0.69.4 */
+pub const A: u32 = 1;
+// Not generated, just a comment about @generated markers
+"#;
+
+        let FileStats {
+            stats,
+            generated: reason,
+        } = Visitor::from_reader(
+            file.as_bytes(),
+            VisitorContext::Main,
+            &VisitorOptions::default(),
+        )
+        .visit_file();
+        assert_eq!(reason, Some(GeneratedReason::Bindgen));
+        assert_eq!(stats[Category::Generated].sum(), 4);
+
+        let file = "//! Detects `@generated` markers.\npub const A: u32 = 1;\n// @generated\n";
+        let FileStats {
+            stats,
+            generated: reason,
+        } = Visitor::from_reader(
+            file.as_bytes(),
+            VisitorContext::Main,
+            &VisitorOptions::default(),
+        )
+        .visit_file();
+        assert_eq!(reason, None);
+        assert_eq!(stats[Category::Main].sum(), 3);
+    }
+
+    #[test]
+    fn out_dir_includes() {
+        let file = r#"
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/proto.rs"));
+}
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/bindings.rs"
+));
+
+include!("manual.rs");
+const INCLUDE: u32 = 1;
+"#;
+
+        let FileStats {
+            stats,
+            generated: reason,
+        } = Visitor::from_reader(
+            file.as_bytes(),
+            VisitorContext::Main,
+            &VisitorOptions::default(),
+        )
+        .visit_file();
+
+        assert_eq!(reason, Some(GeneratedReason::OutDirInclude));
+        assert_eq!(stats[Category::Generated].code, 5);
+        assert_eq!(stats[Category::Main].code, 4);
+        assert_eq!(stats[Category::Main].whitespaces, 3);
     }
 }
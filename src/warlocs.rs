@@ -1,49 +1,292 @@
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::{
+    collections::BTreeMap,
+    fmt::Display,
     iter::Sum,
-    ops::{Add, AddAssign},
+    ops::{Add, AddAssign, Index, Sub, SubAssign},
+    str::FromStr,
 };
 
-#[derive(Debug, Default, Copy, Clone, Serialize)]
+use crate::{generated::GeneratedReason, visitor::VisitorContext};
+
+/// Category lines of code are counted in. Built-in categories come first, in a fixed order, then
+/// user-defined categories ordered by name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Main,
+    Tests,
+    Examples,
+    Benches,
+    BuildScripts,
+    Generated,
+    /// Category assigned by user-defined rules, e.g. `test-data`.
+    Custom(String),
+}
+
+/// Line counts split by category. Built-in categories are always reported, with zero counts if no
+/// lines were counted in them, user-defined categories only once lines were counted in them.
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Warlocs {
-    pub main: Locs,
-    pub tests: Locs,
-    pub examples: Locs,
+    categories: BTreeMap<Category, Locs>,
 }
 
-#[derive(Debug, Default, Copy, Clone, Serialize)]
+/// Stats of a single file.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct FileStats {
+    #[serde(flatten)]
+    pub stats: Warlocs,
+    /// Why the file or some of its regions were counted as generated code. Serialized under its
+    /// own key, `generated` holds the counts of the generated category.
+    #[serde(rename = "generated_reason", skip_serializing_if = "Option::is_none")]
+    pub generated: Option<GeneratedReason>,
+}
+
+/// Line counts by kind of line. Every line is counted once: a line with code and a comment is
+/// a line of code.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
 pub struct Locs {
+    /// Blank lines.
     pub whitespaces: u64,
     pub code: u64,
+    /// Lines of documentation comments.
     pub docs: u64,
+    /// Lines of code blocks in documentation that rustdoc runs as tests.
+    pub doctests: u64,
+    /// Lines of regular comments.
     pub comments: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCategory(pub String);
+
+impl Display for InvalidCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if Category::RESERVED.contains(&self.0.as_str()) {
+            return write!(
+                f,
+                "invalid category `{}`, the name is used by other fields of the serialized output",
+                self.0
+            );
+        }
+        write!(
+            f,
+            "invalid category `{}`, expected one of {} or a name made of letters, digits, `-` \
+             and `_`",
+            self.0,
+            Category::BUILT_IN
+                .iter()
+                .map(Category::name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for InvalidCategory {}
+
+impl Category {
+    pub const BUILT_IN: [Category; 6] = [
+        Self::Main,
+        Self::Tests,
+        Self::Examples,
+        Self::Benches,
+        Self::BuildScripts,
+        Self::Generated,
+    ];
+
+    /// Keys serialized next to the categories, which user-defined categories can't be named
+    /// after.
+    pub const RESERVED: [&'static str; 13] = [
+        "file_count",
+        "generated_reason",
+        "files",
+        "totals",
+        "unreachable",
+        "name",
+        "path",
+        "children",
+        "roots",
+        "packages",
+        "targets",
+        "dependencies",
+        "dirs",
+    ];
+
+    /// Name of the category in serialized output and in rules.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Main => "main",
+            Self::Tests => "tests",
+            Self::Examples => "examples",
+            Self::Benches => "benches",
+            Self::BuildScripts => "build_scripts",
+            Self::Generated => "generated",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Human-readable name of the category.
+    pub fn title(&self) -> &str {
+        match self {
+            Self::Main => "Main",
+            Self::Tests => "Tests",
+            Self::Examples => "Examples",
+            Self::Benches => "Benches",
+            Self::BuildScripts => "Build scripts",
+            Self::Generated => "Generated",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Returns the context the visitor counts code of the category in. `None` for user-defined
+    /// categories.
+    pub fn context(&self) -> Option<VisitorContext> {
+        match self {
+            Self::Main => Some(VisitorContext::Main),
+            Self::Tests => Some(VisitorContext::Tests),
+            Self::Examples => Some(VisitorContext::Example),
+            Self::Benches => Some(VisitorContext::Benches),
+            Self::BuildScripts => Some(VisitorContext::BuildScript),
+            Self::Generated => Some(VisitorContext::Generated),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
+impl From<VisitorContext> for Category {
+    fn from(context: VisitorContext) -> Self {
+        match context {
+            VisitorContext::Main => Self::Main,
+            VisitorContext::Tests => Self::Tests,
+            VisitorContext::Example => Self::Examples,
+            VisitorContext::Benches => Self::Benches,
+            VisitorContext::BuildScript => Self::BuildScripts,
+            VisitorContext::Generated => Self::Generated,
+        }
+    }
+}
+
+impl FromStr for Category {
+    type Err = InvalidCategory;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(category) = Self::BUILT_IN
+            .into_iter()
+            .find(|category| category.name() == name)
+        {
+            return Ok(category);
+        }
+
+        let is_valid = !name.is_empty()
+            && !Self::RESERVED.contains(&name)
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if is_valid {
+            Ok(Self::Custom(name.to_string()))
+        } else {
+            Err(InvalidCategory(name.to_string()))
+        }
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl Warlocs {
+    /// Returns the counts of the category.
+    pub fn get(&self, category: &Category) -> Locs {
+        self.categories.get(category).copied().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, category: Category) -> &mut Locs {
+        self.categories.entry(category).or_default()
+    }
+
+    /// Removes the counts of the category and returns them.
+    pub fn remove(&mut self, category: &Category) -> Locs {
+        self.categories.remove(category).unwrap_or_default()
+    }
+
+    /// Moves the counts of a category to another one.
+    pub fn move_category(&mut self, from: &Category, to: Category) {
+        let locs = self.remove(from);
+        *self.get_mut(to) += locs;
+    }
+
+    /// Returns the counts of all built-in categories and of the user-defined categories with
+    /// counted lines, in the order of the categories.
+    pub fn iter(&self) -> impl Iterator<Item = (&Category, Locs)> {
+        let custom = self
+            .categories
+            .iter()
+            .filter(|(category, _)| matches!(category, Category::Custom(_)))
+            .map(|(category, locs)| (category, *locs));
+        Category::BUILT_IN
+            .iter()
+            .map(|category| (category, self.get(category)))
+            .chain(custom)
+    }
+
+    /// Sum of the counts of all categories.
+    pub fn total(&self) -> Locs {
+        self.categories.values().copied().sum()
+    }
+
     pub fn whitespaces(&self) -> u64 {
-        self.main.whitespaces + self.tests.whitespaces + self.examples.whitespaces
+        self.total().whitespaces
     }
 
     pub fn code(&self) -> u64 {
-        self.main.code + self.tests.code + self.examples.code
+        self.total().code
     }
 
     pub fn docs(&self) -> u64 {
-        self.main.docs + self.tests.docs + self.examples.docs
+        self.total().docs
+    }
+
+    pub fn doctests(&self) -> u64 {
+        self.total().doctests
     }
 
     pub fn comments(&self) -> u64 {
-        self.main.comments + self.tests.comments + self.examples.comments
+        self.total().comments
     }
 
     pub fn sum(&self) -> u64 {
-        self.whitespaces() + self.code() + self.docs() + self.comments()
+        self.total().sum()
     }
 }
 
 impl Locs {
     pub fn sum(&self) -> u64 {
-        self.whitespaces + self.code + self.docs + self.comments
+        self.whitespaces + self.code + self.docs + self.doctests + self.comments
+    }
+}
+
+/// Returns zero counts for categories without counted lines.
+impl Index<Category> for Warlocs {
+    type Output = Locs;
+
+    fn index(&self, category: Category) -> &Self::Output {
+        const EMPTY: &Locs = &Locs {
+            whitespaces: 0,
+            code: 0,
+            docs: 0,
+            doctests: 0,
+            comments: 0,
+        };
+        self.categories.get(&category).unwrap_or(EMPTY)
+    }
+}
+
+/// Serializes the counts as a map from the name of each category, see [`Warlocs::iter`].
+impl Serialize for Warlocs {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter().map(|(category, locs)| (category.name(), locs)))
     }
 }
 
@@ -53,21 +296,41 @@ impl Sum for Warlocs {
     }
 }
 
+impl<'a> Sum<&'a Warlocs> for Warlocs {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Warlocs::default(), |mut sum, stats| {
+            sum += stats;
+            sum
+        })
+    }
+}
+
 impl Add<Warlocs> for Warlocs {
     type Output = Self;
 
-    fn add(self, rhs: Warlocs) -> Self::Output {
-        Self {
-            main: self.main + rhs.main,
-            tests: self.tests + rhs.tests,
-            examples: self.examples + rhs.examples,
-        }
+    fn add(mut self, rhs: Warlocs) -> Self::Output {
+        self += &rhs;
+        self
     }
 }
 
 impl AddAssign<Warlocs> for Warlocs {
     fn add_assign(&mut self, rhs: Warlocs) {
-        *self = *self + rhs;
+        *self += &rhs;
+    }
+}
+
+impl AddAssign<&Warlocs> for Warlocs {
+    fn add_assign(&mut self, rhs: &Warlocs) {
+        for (category, locs) in &rhs.categories {
+            *self.get_mut(category.clone()) += *locs;
+        }
+    }
+}
+
+impl Sum for Locs {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Locs::default(), Locs::add)
     }
 }
 
@@ -79,7 +342,34 @@ impl Add<Locs> for Locs {
             whitespaces: self.whitespaces + rhs.whitespaces,
             code: self.code + rhs.code,
             docs: self.docs + rhs.docs,
+            doctests: self.doctests + rhs.doctests,
             comments: self.comments + rhs.comments,
         }
     }
 }
+
+impl AddAssign<Locs> for Locs {
+    fn add_assign(&mut self, rhs: Locs) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Locs> for Locs {
+    type Output = Self;
+
+    fn sub(self, rhs: Locs) -> Self::Output {
+        Self {
+            whitespaces: self.whitespaces - rhs.whitespaces,
+            code: self.code - rhs.code,
+            docs: self.docs - rhs.docs,
+            doctests: self.doctests - rhs.doctests,
+            comments: self.comments - rhs.comments,
+        }
+    }
+}
+
+impl SubAssign<Locs> for Locs {
+    fn sub_assign(&mut self, rhs: Locs) {
+        *self = *self - rhs;
+    }
+}
@@ -0,0 +1,284 @@
+//! Cargo workspaces read with `cargo metadata`.
+
+use std::{
+    env,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Deserialize;
+
+use crate::manifest::{Package, Target, TargetKind};
+
+/// Packages of a Cargo workspace.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    /// All members of the workspace.
+    pub packages: Vec<Package>,
+    /// Names of the packages selected when no package is given explicitly, like
+    /// `default-members`.
+    default_members: Vec<String>,
+}
+
+/// Packages to count, following the package selection flags of Cargo.
+#[derive(Debug, Default, Clone)]
+pub struct PackageSelection {
+    /// Names of the packages given with `-p/--package`.
+    pub packages: Vec<String>,
+    /// Selects all members of the workspace.
+    pub workspace: bool,
+    /// Names of the packages to leave out when selecting the whole workspace.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum WorkspaceError {
+    /// `cargo` could not be started.
+    Cargo(io::Error),
+    /// `cargo metadata` failed with the given error output.
+    Metadata(String),
+    Parse(serde_json::Error),
+    UnknownPackage(String),
+}
+
+impl Display for WorkspaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cargo(e) => write!(f, "failed to run cargo metadata: {e}"),
+            Self::Metadata(stderr) => write!(f, "cargo metadata failed: {}", stderr.trim()),
+            Self::Parse(e) => write!(f, "failed to parse output of cargo metadata: {e}"),
+            Self::UnknownPackage(name) => {
+                write!(f, "package `{name}` is not a member of the workspace")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkspaceError {}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    workspace_members: Vec<String>,
+    workspace_default_members: Option<Vec<String>>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataTarget {
+    kind: Vec<String>,
+    name: String,
+    src_path: PathBuf,
+}
+
+impl Workspace {
+    /// Reads the workspace of the given `Cargo.toml`, or of the current directory, by running
+    /// `cargo metadata`.
+    pub fn load(manifest_path: Option<&Path>) -> Result<Self, WorkspaceError> {
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut command = Command::new(cargo);
+        command.args(["metadata", "--format-version", "1", "--no-deps"]);
+        if let Some(manifest_path) = manifest_path {
+            command.arg("--manifest-path").arg(manifest_path);
+        }
+
+        let output = command.output().map_err(WorkspaceError::Cargo)?;
+        if !output.status.success() {
+            return Err(WorkspaceError::Metadata(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+
+        let metadata: Metadata =
+            serde_json::from_slice(&output.stdout).map_err(WorkspaceError::Parse)?;
+        Ok(Self::from_metadata(metadata))
+    }
+
+    fn from_metadata(metadata: Metadata) -> Self {
+        let default_members = metadata
+            .workspace_default_members
+            .unwrap_or_else(|| metadata.workspace_members.clone());
+        let mut packages = vec![];
+        let mut default_member_names = vec![];
+
+        for package in metadata.packages {
+            if !metadata.workspace_members.contains(&package.id) {
+                continue;
+            }
+            if default_members.contains(&package.id) {
+                default_member_names.push(package.name.clone());
+            }
+
+            let targets = package
+                .targets
+                .into_iter()
+                .filter_map(|target| {
+                    let kind = target.kind.iter().find_map(|kind| target_kind(kind))?;
+                    Some(Target {
+                        kind,
+                        name: target.name,
+                        root: target.src_path,
+                    })
+                })
+                .collect();
+
+            packages.push(Package {
+                name: package.name,
+                dir: package
+                    .manifest_path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf(),
+                targets,
+            });
+        }
+
+        Self {
+            root: metadata.workspace_root,
+            packages,
+            default_members: default_member_names,
+        }
+    }
+
+    /// Returns the selected packages in the order of the workspace members.
+    pub fn select(&self, selection: &PackageSelection) -> Result<Vec<&Package>, WorkspaceError> {
+        for name in selection.packages.iter().chain(&selection.exclude) {
+            if !self.packages.iter().any(|package| &package.name == name) {
+                return Err(WorkspaceError::UnknownPackage(name.clone()));
+            }
+        }
+
+        let is_selected = |package: &Package| {
+            if selection.workspace {
+                !selection.exclude.contains(&package.name)
+            } else if !selection.packages.is_empty() {
+                selection.packages.contains(&package.name)
+            } else {
+                self.default_members.contains(&package.name)
+            }
+        };
+
+        Ok(self
+            .packages
+            .iter()
+            .filter(|package| is_selected(package))
+            .collect())
+    }
+
+    /// Returns the package a file belongs to: the package with the innermost directory
+    /// containing the file.
+    pub fn owner(&self, path: impl AsRef<Path>) -> Option<&Package> {
+        let path = path.as_ref();
+        self.packages
+            .iter()
+            .filter(|package| path.starts_with(&package.dir))
+            .max_by_key(|package| package.dir.components().count())
+    }
+}
+
+/// Converts a target kind from `cargo metadata`. Libraries of all crate types are libraries.
+fn target_kind(kind: &str) -> Option<TargetKind> {
+    match kind {
+        "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro" => Some(TargetKind::Lib),
+        "bin" => Some(TargetKind::Bin),
+        "test" => Some(TargetKind::Test),
+        "example" => Some(TargetKind::Example),
+        "bench" => Some(TargetKind::Bench),
+        "custom-build" => Some(TargetKind::BuildScript),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempTree;
+
+    fn workspace() -> (TempTree, Workspace) {
+        let tree = TempTree::new("workspace");
+        let root = tree.path();
+
+        tree.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\ndefault-members = [\"crates/a\"]\n",
+        );
+        tree.write("crates/a/Cargo.toml", "[package]\nname = \"a\"\n");
+        tree.write("crates/a/src/lib.rs", "");
+        tree.write(
+            "crates/b/Cargo.toml",
+            "[package]\nname = \"b\"\n\n[[test]]\nname = \"it\"\npath = \"it/main.rs\"\n",
+        );
+        tree.write("crates/b/src/main.rs", "");
+        tree.write("crates/b/it/main.rs", "");
+
+        let workspace = Workspace::load(Some(&root.join("Cargo.toml"))).unwrap();
+        (tree, workspace)
+    }
+
+    #[test]
+    fn load_and_select() {
+        let (_tree, workspace) = workspace();
+
+        let names = |selection: &PackageSelection| -> Vec<String> {
+            workspace
+                .select(selection)
+                .unwrap()
+                .into_iter()
+                .map(|package| package.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(&PackageSelection::default()), vec!["a"]);
+        assert_eq!(
+            names(&PackageSelection {
+                workspace: true,
+                ..Default::default()
+            }),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            names(&PackageSelection {
+                workspace: true,
+                exclude: vec!["a".into()],
+                ..Default::default()
+            }),
+            vec!["b"]
+        );
+        assert_eq!(
+            names(&PackageSelection {
+                packages: vec!["b".into()],
+                ..Default::default()
+            }),
+            vec!["b"]
+        );
+        assert!(workspace
+            .select(&PackageSelection {
+                packages: vec!["c".into()],
+                ..Default::default()
+            })
+            .is_err());
+
+        let b = &workspace.packages[1];
+        assert!(b
+            .targets
+            .iter()
+            .any(|target| target.kind == TargetKind::Test && target.root.ends_with("it/main.rs")));
+        assert_eq!(
+            workspace
+                .owner(b.dir.join("src/main.rs"))
+                .map(|package| package.name.as_str()),
+            Some("b")
+        );
+    }
+}